@@ -4,5 +4,5 @@ fn main() {
     // #[cfg(target_arch = "wasm32")]
     // console_error_panic_hook::set_once();
 
-    client::run();
+    client::run(client::ClientSettings::default());
 }
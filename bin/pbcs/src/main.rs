@@ -1,7 +1,8 @@
 use core::{
-    components::{Ball, GoalieBehavior},
+    components::{Ball, GoalieBehavior, PointZone},
     constants::*,
     debug::uv_texture,
+    rules::{KickModel, RulesPlugin, RulesSet, ShotOutcome, ShotOutcomeKind},
     systems::{goalie, magnus_effect},
 };
 
@@ -121,76 +122,47 @@ pub fn game_app() {
         .add_event::<ControllerEvent>()
         .add_systems((debug_overlay, controls, game_logic).in_base_set(GameSet))
         .configure_set(GameSet.run_if(in_state(AppState::InGame)))
+        .add_plugin(RulesPlugin)
+        .configure_set(RulesSet.after(GameSet).run_if(in_state(AppState::InGame)))
         // .add_system(bevy::window::close_on_esc)
         .run();
 }
 
-//#TODO move server's physics systems into core and remove this giant game_logic system
 pub fn game_logic(
-    time: Res<Time>,
     mut game: ResMut<Game>,
-    mut ball_query: Query<(
-        &mut Transform,
-        &mut Ball,
-        &mut ExternalForce,
-        &mut ExternalImpulse,
-        &mut Velocity,
-    )>,
-
-    mut collision_events: EventReader<CollisionEvent>,
+    kick_model: Res<KickModel>,
+    mut ball_query: Query<(&Transform, &mut Ball, &mut ExternalImpulse)>,
+
+    mut outcome_events: EventReader<ShotOutcome>,
     mut controller_events: EventReader<ControllerEvent>,
 ) {
-    let (mut transform, mut ball, mut ext_f, mut ext_i, mut velocity) =
-        ball_query.get_mut(game.ball_entity.unwrap()).unwrap();
+    let (transform, mut ball, mut ext_i) = ball_query.get_mut(game.ball_entity.unwrap()).unwrap();
 
-    for _event in collision_events.iter() {
-        // log::info!("Received collision event: {:?}", event);
-        game.goal = true;
+    for outcome in outcome_events.iter() {
+        if outcome.kind == ShotOutcomeKind::Goal {
+            game.goal = true;
+        }
     }
 
-    let mut should_reset = false;
     for event in controller_events.iter() {
         match event {
             ControllerEvent::Reset => {
-                should_reset = true;
+                log::info!("BALL RESET");
+                ball.force_reset = true;
             }
             ControllerEvent::Shoot {
                 ray_normal,
                 ray_point,
             } => {
                 log::info!("SHOOT {:?} - {:?}", ray_normal, ray_point);
-                let ray_normal = Vec3::new(ray_normal.x, ray_normal.y - 0.8, ray_normal.z);
-                let kick_force = Vec3::new(-2.0, -3.0, -13.0);
-                let impulse = ray_normal * kick_force;
-                // let impulse_camera = camera_rotation.normalize() * force.neg();
-                *ext_i = ExternalImpulse::at_point(impulse, *ray_point, transform.translation);
-                ext_i.torque_impulse = ext_i.torque_impulse * 0.15;
-                // ext_i.impulse = impulse_camera;
-                game.shot = true;
-                // screen_print!(col: Color::CYAN, "ray_point: {}", ray.point);
-                // screen_print!(col: Color::CYAN, "ray_normal: {}", ray.normal);
-                // screen_print!(col: Color::CYAN, "ray_normal adjusted: {}", ray_normal);
-                // screen_print!(col: Color::CYAN, "impulse: {}", impulse);
+                kick_model.kick(*ray_normal, *ray_point, transform, &mut ball, &mut ext_i);
             }
         }
     }
 
-    if game.shot && !game.goal {
-        // reset the ball if it has been shot with no goal after BALL_SHOT_WAIT_TIME seconds
-        ball.shot_elapsed += time.delta_seconds();
-        if ball.shot_elapsed >= BALL_SHOT_WAIT_TIME {
-            should_reset = true;
-        }
-    }
-
-    if should_reset {
-        log::info!("BALL RESET");
-        *ext_f = ExternalForce::default();
-        *ext_i = ExternalImpulse::default();
-        *velocity = Velocity::zero();
-        *transform = Transform::from_translation(BALL_START);
-        ball.shot_elapsed = 0.0;
-        game.shot = false;
+    // the ball itself is reset by the shared rules in `core::rules::ball_reset`
+    game.shot = ball.shot;
+    if !ball.shot {
         game.goal = false;
     }
 }
@@ -525,6 +497,7 @@ pub fn staging(
             ));
             p.spawn((
                 Name::new("PointZone"),
+                PointZone,
                 TransformBundle::from(Transform::from_xyz(0.0, rad * 5.0, (-rad * 0.5) - rad)),
                 Sensor,
                 Collider::cuboid(rad * 10.0, rad * 5.0, rad * 0.5),
@@ -1,7 +1,9 @@
 use core::{
-    components::{Ball, GoalieBehavior},
+    components::{Ball, GoalieBehavior, PointZone},
     constants::*,
     debug::uv_texture,
+    goalie_ai::Difficulty,
+    rules::{KickModel, RulesPlugin, RulesSet, ShotOutcome, ShotOutcomeKind},
     systems::{goalie, magnus_effect},
 };
 
@@ -12,7 +14,7 @@ use core::{
 /// https://github.com/alexichepura/bevy_garage/blob/main/src/car.rs
 use std::f32::consts::*;
 
-use clap::Parser;
+use clap::{Args, Parser};
 
 use bevy::{prelude::*, render::camera::RenderTarget, window::PrimaryWindow};
 use bevy_turborand::prelude::*;
@@ -46,13 +48,124 @@ pub struct Cli {
 #[derive(Debug, Parser)]
 pub enum Subcommand {
     /// original single player game
-    Single,
+    Single {
+        /// how well the goalie plays: easy, normal or hard
+        #[clap(long, env = "POWERBABY_GOALIE", default_value = "easy")]
+        goalie: Difficulty,
+    },
     /// run client only
-    Client,
+    Client(ClientArgs),
+    /// watch every ball of a room without playing, e.g. on a TV
+    Spectate(ClientArgs),
+    /// play the goalie of a room against the others' kicks
+    Goalie(ClientArgs),
     /// run server only
-    Server,
+    Server(ServerArgs),
     /// run standalone with server + client
-    Standalone,
+    Standalone(ServerArgs),
+    /// print a signed player token for `--auth-secret`
+    IssueToken {
+        /// stable id of the player, e.g. the id from the invite list
+        player_id: String,
+        #[clap(long, env = "POWERBABY_AUTH_SECRET")]
+        auth_secret: String,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ClientArgs {
+    /// server signaling url to connect to
+    #[clap(long, env = "POWERBABY_SERVER", default_value = protocol::SERVER_HANDSHAKE_URL)]
+    pub server: String,
+    /// signed player token, see `issue-token`
+    #[clap(long, env = "POWERBABY_TOKEN")]
+    pub token: Option<String>,
+    /// code of the match to join, the main room when missing
+    #[clap(long, env = "POWERBABY_ROOM")]
+    pub room: Option<String>,
+    /// extra milliseconds other balls are drawn in the past, more hides more packet jitter
+    #[clap(long, env = "POWERBABY_INTERP_DELAY_MS", default_value_t = 100)]
+    pub interp_delay_ms: u64,
+}
+
+impl From<ClientArgs> for client::ClientSettings {
+    fn from(args: ClientArgs) -> Self {
+        Self {
+            server_url: args.server,
+            token: args.token,
+            room: args.room,
+            interp_delay: std::time::Duration::from_millis(args.interp_delay_ms),
+            spectate: false,
+            goalie: false,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ServerArgs {
+    /// address the WebRTC signaling server listens on
+    #[clap(long, env = "POWERBABY_SIGNAL_ADDR", default_value = "0.0.0.0:14191")]
+    pub signal_addr: std::net::SocketAddr,
+    /// address the WebRTC data channels listen on
+    #[clap(long, env = "POWERBABY_DATA_ADDR", default_value = "0.0.0.0:14192")]
+    pub data_addr: std::net::SocketAddr,
+    /// public WebRTC data url advertised to clients
+    #[clap(long, env = "POWERBABY_PUBLIC_URL", default_value = protocol::SERVER_AD_URL)]
+    pub public_url: String,
+    /// score store, a json file path or `sqlite:<path>`
+    #[clap(long, env = "POWERBABY_STORE", default_value = "./powerbaby.json")]
+    pub store: String,
+    /// save scores on every goal instead of every 30 seconds
+    #[clap(long, env = "POWERBABY_WRITE_THROUGH")]
+    pub write_through: bool,
+    /// shared secret for signed player tokens, players are anonymous without it
+    #[clap(long, env = "POWERBABY_AUTH_SECRET")]
+    pub auth_secret: Option<String>,
+    /// reject players without a valid token
+    #[clap(long, env = "POWERBABY_REQUIRE_TOKEN", requires = "auth_secret")]
+    pub require_token: bool,
+    /// players past this are turned away
+    #[clap(long, env = "POWERBABY_MAX_PLAYERS", default_value_t = 64)]
+    pub max_players: usize,
+    /// file with one blocked word per line
+    #[clap(long, env = "POWERBABY_NAME_BLOCKLIST")]
+    pub name_blocklist: Option<std::path::PathBuf>,
+    /// what to do with a name that is already playing: reject or suffix
+    #[clap(long, env = "POWERBABY_NAME_COLLISION", default_value = "reject")]
+    pub name_collision: server::names::NameCollision,
+    /// balls sent to each player: all, in-flight, recent or recent:<count>. own ball is always sent
+    #[clap(long, env = "POWERBABY_SCOPE", default_value = "all")]
+    pub scope: server::scope::ScopePolicy,
+    /// seconds a dropped player's ball waits for them to reconnect, 0 despawns it right away
+    #[clap(long, env = "POWERBABY_RESUME_GRACE_SECS", default_value_t = 30)]
+    pub resume_grace_secs: u64,
+    /// read admin commands (list, kick, ban, scores, announce, pause) from stdin
+    #[clap(long, env = "POWERBABY_ADMIN_CONSOLE")]
+    pub admin_console: bool,
+    /// goalie of new rooms: easy, normal or hard
+    #[clap(long, env = "POWERBABY_GOALIE", default_value = "easy")]
+    pub goalie: Difficulty,
+}
+
+impl From<ServerArgs> for server::settings::ServerSettings {
+    fn from(args: ServerArgs) -> Self {
+        Self {
+            signal_addr: args.signal_addr,
+            data_addr: args.data_addr,
+            public_url: args.public_url,
+            store: args.store,
+            write_through: args.write_through,
+            auth_secret: args.auth_secret,
+            require_token: args.require_token,
+            max_players: args.max_players,
+            name_blocklist: args.name_blocklist,
+            name_collision: args.name_collision,
+            scope: args.scope,
+            resume_grace: std::time::Duration::from_secs(args.resume_grace_secs),
+            admin_console: args.admin_console,
+            goalie: args.goalie,
+        }
+    }
 }
 
 // #[derive(Resource, Reflect, InspectorOptions, Default)]
@@ -66,6 +179,7 @@ pub struct Game {
     goal: bool,
     shot: bool,
     camera_is_birdseye: bool,
+    difficulty: Difficulty,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default, States)]
@@ -96,19 +210,77 @@ pub enum ControllerEvent {
 fn main() {
     let cli = Cli::parse();
     match cli.subcommand {
-        Subcommand::Single => game_app(),
-        Subcommand::Client => client::run(),
-        Subcommand::Server => server::run(),
-        Subcommand::Standalone => {
-            std::thread::spawn(|| server::run());
-            client::run();
+        Subcommand::Single { goalie } => game_app(goalie),
+        Subcommand::Client(args) => client::run(args.into()),
+        Subcommand::Spectate(args) => {
+            let mut settings: client::ClientSettings = args.into();
+            settings.spectate = true;
+            client::run(settings);
+        }
+        Subcommand::Goalie(args) => {
+            let mut settings: client::ClientSettings = args.into();
+            settings.goalie = true;
+            client::run(settings);
+        }
+        Subcommand::Server(args) => run_server(args.into(), shutdown_on_signal()),
+        Subcommand::Standalone(args) => {
+            let client_settings = client::ClientSettings {
+                server_url: format!("http://127.0.0.1:{}", args.signal_addr.port()),
+                token: None,
+                room: None,
+                interp_delay: client::snapshot::DEFAULT_INTERP_DELAY,
+                spectate: false,
+                goalie: false,
+            };
+            let shutdown = shutdown_on_signal();
+            std::thread::spawn(move || {
+                run_server(args.into(), shutdown);
+                // nothing left for the client to play against
+                std::process::exit(0);
+            });
+            client::run(client_settings);
         }
+        Subcommand::IssueToken {
+            player_id,
+            auth_secret,
+        } => {
+            let authenticator = server::auth::HmacAuthenticator::new(auth_secret, false);
+            println!("{}", authenticator.issue(&player_id));
+        }
+    }
+}
+
+/// The first SIGINT/SIGTERM asks the server to save the scores and stop, the second one exits
+/// right away
+fn shutdown_on_signal() -> server::Shutdown {
+    let shutdown = server::Shutdown::default();
+    let handler = shutdown.clone();
+    let installed = ctrlc::set_handler(move || {
+        if handler.is_requested() {
+            std::process::exit(130);
+        }
+        handler.request();
+    });
+    if let Err(err) = installed {
+        eprintln!("can't install the shutdown handler, scores won't be saved on exit: {err}");
+    }
+    shutdown
+}
+
+/// The server refuses to start on unreadable scores, exit instead of leaving the client hanging
+fn run_server(settings: server::settings::ServerSettings, shutdown: server::Shutdown) {
+    if let Err(err) = server::run(settings, shutdown) {
+        eprintln!("server error: {err:#}");
+        std::process::exit(1);
     }
 }
 
-pub fn game_app() {
+pub fn game_app(difficulty: Difficulty) {
     App::new()
-        .init_resource::<Game>()
+        .insert_resource(Game {
+            difficulty,
+            ..default()
+        })
         // .register_type::<Game>()
         // .register_type::<Cow<'static, str>>()
         // .register_type::<time::Duration>()
@@ -154,76 +326,47 @@ pub fn game_app() {
         .add_event::<ControllerEvent>()
         .add_systems((debug_overlay, controls, game_logic).in_base_set(GameSet))
         .configure_set(GameSet.run_if(in_state(AppState::InGame)))
+        .add_plugin(RulesPlugin)
+        .configure_set(RulesSet.after(GameSet).run_if(in_state(AppState::InGame)))
         .add_system(bevy::window::close_on_esc)
         .run();
 }
 
-//#TODO move server's physics systems into core and remove this giant game_logic system
 pub fn game_logic(
-    time: Res<Time>,
     mut game: ResMut<Game>,
-    mut ball_query: Query<(
-        &mut Transform,
-        &mut Ball,
-        &mut ExternalForce,
-        &mut ExternalImpulse,
-        &mut Velocity,
-    )>,
-
-    mut collision_events: EventReader<CollisionEvent>,
+    kick_model: Res<KickModel>,
+    mut ball_query: Query<(&Transform, &mut Ball, &mut ExternalImpulse)>,
+
+    mut outcome_events: EventReader<ShotOutcome>,
     mut controller_events: EventReader<ControllerEvent>,
 ) {
-    let (mut transform, mut ball, mut ext_f, mut ext_i, mut velocity) =
-        ball_query.get_mut(game.ball_entity.unwrap()).unwrap();
+    let (transform, mut ball, mut ext_i) = ball_query.get_mut(game.ball_entity.unwrap()).unwrap();
 
-    for _event in collision_events.iter() {
-        // log::info!("Received collision event: {:?}", event);
-        game.goal = true;
+    for outcome in outcome_events.iter() {
+        if outcome.kind == ShotOutcomeKind::Goal {
+            game.goal = true;
+        }
     }
 
-    let mut should_reset = false;
     for event in controller_events.iter() {
         match event {
             ControllerEvent::Reset => {
-                should_reset = true;
+                log::info!("BALL RESET");
+                ball.force_reset = true;
             }
             ControllerEvent::Shoot {
                 ray,
                 camera_rotation: _,
             } => {
                 log::info!("SHOOT {:?}", ray);
-                let ray_normal = Vec3::new(ray.normal.x, ray.normal.y - 0.8, ray.normal.z);
-                let kick_force = Vec3::new(-2.0, -3.0, -13.0);
-                let impulse = ray_normal * kick_force;
-                // let impulse_camera = camera_rotation.normalize() * force.neg();
-                *ext_i = ExternalImpulse::at_point(impulse, ray.point, transform.translation);
-                ext_i.torque_impulse = ext_i.torque_impulse * 0.15;
-                // ext_i.impulse = impulse_camera;
-                game.shot = true;
-                // screen_print!(col: Color::CYAN, "ray_point: {}", ray.point);
-                // screen_print!(col: Color::CYAN, "ray_normal: {}", ray.normal);
-                // screen_print!(col: Color::CYAN, "ray_normal adjusted: {}", ray_normal);
-                // screen_print!(col: Color::CYAN, "impulse: {}", impulse);
+                kick_model.kick(ray.normal, ray.point, transform, &mut ball, &mut ext_i);
             }
         }
     }
 
-    if game.shot && !game.goal {
-        // reset the ball if it has been shot with no goal after BALL_SHOT_WAIT_TIME seconds
-        ball.shot_elapsed += time.delta_seconds();
-        if ball.shot_elapsed >= BALL_SHOT_WAIT_TIME {
-            should_reset = true;
-        }
-    }
-
-    if should_reset {
-        log::info!("BALL RESET");
-        *ext_f = ExternalForce::default();
-        *ext_i = ExternalImpulse::default();
-        *velocity = Velocity::zero();
-        *transform = Transform::from_translation(BALL_START);
-        ball.shot_elapsed = 0.0;
-        game.shot = false;
+    // the ball itself is reset by the shared rules in `core::rules::ball_reset`
+    game.shot = ball.shot;
+    if !ball.shot {
         game.goal = false;
     }
 }
@@ -532,6 +675,7 @@ pub fn staging(
             ));
             p.spawn((
                 Name::new("PointZone"),
+                PointZone,
                 TransformBundle::from(Transform::from_xyz(0.0, rad * 5.0, (-rad * 0.5) - rad)),
                 Sensor,
                 Collider::cuboid(rad * 10.0, rad * 5.0, rad * 0.5),
@@ -549,7 +693,7 @@ pub fn staging(
         commands
             .spawn((
                 Name::new("Goalie"),
-                GoalieBehavior::default(),
+                GoalieBehavior::new(game.difficulty),
                 // Sensor,
                 // TransformBundle::from_transform(Transform::from_translation(GOALIE_START)),
                 PbrBundle {
@@ -1,6 +1,10 @@
-use core::constants::*;
+use core::{
+    constants::*,
+    rules::{RulesPlugin, RulesSet},
+    systems::magnus_effect,
+};
 /// # TODO
-/// - goalie jump or bobble or shield (probably shield?)
+/// - goalie bobble or shield (probably shield?)
 /// - asset loading
 /// - render unowned balls names (nice to have?)
 ///
@@ -10,20 +14,19 @@ use core::constants::*;
 /// - Determinism:
 ///     - https://github.com/dimforge/bevy_rapier/issues/79
 ///     - https://github.com/Looooong/doce/blob/67a32acbd8cfbf31c88b253bf5991a17da5d06bc/src/main.rs
-/// - Lag compensation on server (maybe, this is hard)
-///     - store X ticks of server state
-///     - when a kick arrives, simulate from the kick tick that the client THOUGHT he saw
-///     - if the ball hits the goal or completely misses, do nothing and let it score
+/// - Lag compensation on server (see `server::lag_compensation`)
+///     - the server stores X ticks of goalie/ball state and checks kicks against the tick the
+///     client saw.  Shots the client saw going past the goalie ignore the goalie.
 ///     - if the ball is "bounced" by the goalie, stitch this physics into the current physics
 ///     engine.  (this is hard/unknown for bevy_rapier.  Not sure how to do this but the ball
 ///     basically needs to fly as if it was bounced off at that past point, into the future
 ///     somehow.  The current physics will need to keep moving forward)
 use protocol::{
-    messages::{Auth, KeyCommand},
-    primitives::Scores,
+    messages::KeyCommand,
+    primitives::{PlayerStats, Scores, ServerFeature},
 };
 
-use std::f32::consts::*;
+use std::{f32::consts::*, time::Duration};
 
 // use bevy::input::InputPlugin;
 // use bevy::log::LogPlugin;
@@ -90,6 +93,143 @@ fn get_userinfo() -> anyhow::Result<(String, String)> {
     Ok(("armorous0772".to_owned(), "blue".to_owned()))
 }
 
+/// `window.server_url` wins over the `?server=` query parameter so the hosting page can pin it
+#[cfg(target_arch = "wasm32")]
+fn get_server_url() -> anyhow::Result<String> {
+    use anyhow::anyhow;
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+    use web_sys::UrlSearchParams;
+
+    let window = web_sys::window().ok_or_else(|| anyhow!("Can't access Window object"))?;
+    if let Some(server_url) = Reflect::get(&window, &JsValue::from_str("server_url"))
+        .ok()
+        .and_then(|v| v.as_string())
+    {
+        return Ok(server_url);
+    }
+
+    let search = window
+        .location()
+        .search()
+        .map_err(|_| anyhow!("can't read location search"))?;
+    UrlSearchParams::new_with_str(&search)
+        .map_err(|_| anyhow!("can't parse query string"))?
+        .get("server")
+        .ok_or_else(|| anyhow!("no server_url"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_server_url() -> anyhow::Result<String> {
+    Err(anyhow::anyhow!(
+        "native clients pass the server url on the command line"
+    ))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_player_token() -> anyhow::Result<String> {
+    use anyhow::anyhow;
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+
+    let window = web_sys::window().ok_or_else(|| anyhow!("Can't access Window object"))?;
+    Reflect::get(&window, &JsValue::from_str("player_token"))
+        .map_err(|_| anyhow!("no player_token"))?
+        .as_string()
+        .ok_or_else(|| anyhow!("can't convert to string"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_player_token() -> anyhow::Result<String> {
+    Err(anyhow::anyhow!(
+        "native clients pass the token on the command line"
+    ))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn get_room() -> anyhow::Result<String> {
+    use anyhow::anyhow;
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+
+    let window = web_sys::window().ok_or_else(|| anyhow!("Can't access Window object"))?;
+    Reflect::get(&window, &JsValue::from_str("room"))
+        .map_err(|_| anyhow!("no room"))?
+        .as_string()
+        .ok_or_else(|| anyhow!("can't convert to string"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_room() -> anyhow::Result<String> {
+    Err(anyhow::anyhow!(
+        "native clients pass the room on the command line"
+    ))
+}
+
+/// `window.spectate`, set by spectate.html
+#[cfg(target_arch = "wasm32")]
+fn get_spectate() -> bool {
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+
+    web_sys::window()
+        .and_then(|window| Reflect::get(&window, &JsValue::from_str("spectate")).ok())
+        .and_then(|spectate| spectate.as_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_spectate() -> bool {
+    false
+}
+
+/// `window.goalie`, set by goalie.html
+#[cfg(target_arch = "wasm32")]
+fn get_goalie() -> bool {
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+
+    web_sys::window()
+        .and_then(|window| Reflect::get(&window, &JsValue::from_str("goalie")).ok())
+        .and_then(|goalie| goalie.as_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn get_goalie() -> bool {
+    false
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ClientSettings {
+    /// The server's WebRTC signaling (handshake) url
+    pub server_url: String,
+    /// Signed player identity, anonymous without one
+    pub token: Option<String>,
+    /// Code of the match to join, the server's main room without one
+    pub room: Option<String>,
+    /// Extra time other balls and the goalie are drawn in the past to hide late packets
+    pub interp_delay: Duration,
+    /// Watch every ball without playing, see `spectator`
+    pub spectate: bool,
+    /// Play the room's goalie instead of kicking, see `goalie`
+    pub goalie: bool,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        Self {
+            server_url: get_server_url()
+                .unwrap_or_else(|_| protocol::SERVER_HANDSHAKE_URL.to_owned()),
+            token: get_player_token().ok(),
+            room: get_room().ok(),
+            interp_delay: snapshot::DEFAULT_INTERP_DELAY,
+            spectate: get_spectate(),
+            goalie: get_goalie(),
+        }
+    }
+}
+
 pub fn init(
     mut commands: Commands,
     mut client: Client,
@@ -99,6 +239,7 @@ pub fn init(
     audio: Res<Audio>,
     // audio_sinks: Res<Assets<AudioSink>>,
     asset_server: Res<AssetServer>, // mut materials: ResMut<Assets<ColorMaterial>>,
+    settings: Res<ClientSettings>,
 ) {
     let music = asset_server.load("sounds/smw-world.mp3");
     audio.play_with_settings(
@@ -111,13 +252,28 @@ pub fn init(
     );
 
     info!("PowerBaby Connecting");
-    let (player_name, player_color) =
-        get_userinfo().unwrap_or_else(|_| ("Denis".to_owned(), "blue".to_owned()));
-    info!("Player: {}, Color: {}", player_name, player_color);
+    let (player_name, player_color) = if settings.spectate {
+        info!("Spectating");
+        (String::new(), String::new())
+    } else if settings.goalie {
+        info!("Playing the goalie");
+        (String::new(), String::new())
+    } else {
+        let (player_name, player_color) =
+            get_userinfo().unwrap_or_else(|_| ("Denis".to_owned(), "blue".to_owned()));
+        info!("Player: {}, Color: {}", player_name, player_color);
+        (player_name, player_color)
+    };
 
-    client.auth(Auth::from((player_name.clone(), player_color.clone())));
-    let socket = webrtc::Socket::new(protocol::SERVER_HANDSHAKE_URL, client.socket_config());
+    let reconnect = reconnect::Reconnect::new(
+        (!settings.spectate && !settings.goalie)
+            .then(|| (player_name.clone(), player_color.clone())),
+    );
+    client.auth(reconnect.auth(&settings));
+    info!("Server: {}", settings.server_url);
+    let socket = webrtc::Socket::new(&settings.server_url, client.socket_config());
     client.connect(socket);
+    commands.insert_resource(reconnect);
 
     let mut blue_name = Default::default();
     let mut pink_name = Default::default();
@@ -391,8 +547,15 @@ pub fn init(
         ..default()
     });
 
+    let camera_transform = if settings.spectate {
+        BIRDS_EYE_CAM.looking_at(BIRDS_EYE_CAM_LOOK, Vec3::Y)
+    } else if settings.goalie {
+        GOALIE_CAM.looking_at(GOALIE_CAM_LOOK, Vec3::Y)
+    } else {
+        KICK_CAM.looking_at(KICK_CAM_LOOK, Vec3::Y)
+    };
     commands.spawn((Camera3dBundle {
-        transform: KICK_CAM.looking_at(KICK_CAM_LOOK, Vec3::Y),
+        transform: camera_transform,
         ..Default::default()
     },));
 
@@ -503,13 +666,21 @@ pub struct Global {
 
     //Scoring
     pub my_score: u32,
+    /// Lifetime stats, sent by the server
+    pub my_stats: PlayerStats,
     pub total_pink: u32,
     pub total_blue: u32,
     pub own_score_entity: Option<Entity>,
     pub total_pink_entity: Option<Entity>,
     pub total_blue_entity: Option<Entity>,
+    /// Centered message, e.g. why the server rejected us
+    pub notice_entity: Option<Entity>,
+    /// From `ServerInfo`
+    pub server_features: Vec<ServerFeature>,
 
     pub owned_entity: Option<OwnedEntity>,
+    /// Confirmed ball of the latest `PlayerEvent::kicked`
+    pub last_kicked: Option<Entity>,
     pub ground_entity: Option<Entity>,
     pub queued_command: Option<KeyCommand>,
     pub command_history: CommandHistory<KeyCommand>,
@@ -537,249 +708,228 @@ mod components {
 
     #[derive(Component)]
     pub struct Confirmed;
+}
 
-    #[derive(Default, Component)]
-    pub struct InterpPos {
-        interp: f32,
-
-        pub interp_x: f32,
-        pub interp_y: f32,
-        pub interp_z: f32,
+/// Snapshot interpolation for entities the server simulates.  Every `RepPhysics` update is kept
+/// with the server tick it was sent on, and confirmed entities are drawn `SnapshotStats::delay`
+/// ticks in the past between the two snapshots around that time.  When the snapshot after that
+/// hasn't arrived yet the newest one is extrapolated with its velocity for a little while.
+pub mod snapshot {
+    use super::ClientSettings;
+    use protocol::components::RepPhysics;
 
-        last_x: f32,
-        last_y: f32,
-        last_z: f32,
+    use std::{collections::VecDeque, time::Duration};
 
-        pub next_x: f32,
-        pub next_y: f32,
-        pub next_z: f32,
+    use bevy::prelude::*;
+    use naia_bevy_client::{events::UpdateComponentEvents, Client, Tick};
+
+    pub const DEFAULT_INTERP_DELAY: Duration = Duration::from_millis(100);
+    /// Snapshots kept per entity, ~1.3 seconds at the 40ms tick
+    const CAPACITY: usize = 32;
+    /// Ticks to keep moving an entity past its newest snapshot, ~200ms
+    const MAX_EXTRAPOLATION: f32 = 5.0;
+    /// Weight of a new arrival in the lateness and jitter averages
+    const SMOOTHING: f32 = 0.05;
+    /// Seconds between stats log lines
+    const LOG_INTERVAL: f32 = 10.0;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Snapshot {
+        pub tick: Tick,
+        pub translation: Vec3,
+        pub rotation: Quat,
+        /// `None` for entities replicated without a velocity
+        pub linvel: Option<Vec3>,
     }
 
-    impl InterpPos {
-        pub fn new(x: f32, y: f32, z: f32) -> Self {
+    impl Snapshot {
+        pub fn new(tick: Tick, rep_physics: &RepPhysics) -> Self {
             Self {
-                interp: 0.0,
-
-                interp_x: x,
-                interp_y: y,
-                interp_z: z,
-
-                last_x: x,
-                last_y: y,
-                last_z: z,
-
-                next_x: x,
-                next_y: y,
-                next_z: z,
+                tick,
+                translation: rep_physics.translation(),
+                rotation: rep_physics.rotation(),
+                linvel: rep_physics.velocity().map(|velocity| velocity.linvel),
             }
         }
+    }
 
-        pub(crate) fn next(&mut self, next_x: f32, next_y: f32, next_z: f32) {
-            self.interp = 0.0;
-            self.last_x = self.next_x;
-            self.last_y = self.next_y;
-            self.last_z = self.next_z;
+    /// Ticks from `tick` to `now`, negative if `tick` is after `now`
+    fn ticks_since(now: Tick, tick: Tick) -> f32 {
+        now.wrapping_sub(tick) as i16 as f32
+    }
+
+    pub struct Sample {
+        pub translation: Vec3,
+        pub rotation: Quat,
+        /// Moved past the newest snapshot because the next one is late
+        pub extrapolated: bool,
+    }
 
-            self.interp_x = self.next_x;
-            self.interp_y = self.next_y;
-            self.interp_z = self.next_z;
+    /// Snapshots of one entity in tick order.  Never empty.
+    #[derive(Component)]
+    pub struct SnapshotBuffer {
+        snapshots: VecDeque<Snapshot>,
+    }
 
-            self.next_x = next_x;
-            self.next_y = next_y;
-            self.next_z = next_z;
+    impl SnapshotBuffer {
+        pub fn new(snapshot: Snapshot) -> Self {
+            Self {
+                snapshots: VecDeque::from([snapshot]),
+            }
         }
 
-        pub(crate) fn interpolate(&mut self, interpolation: f32) {
-            if self.interp >= 1.0 || interpolation == 0.0 {
-                return;
+        pub fn push(&mut self, snapshot: Snapshot) {
+            let before = self
+                .snapshots
+                .iter()
+                .rposition(|s| ticks_since(snapshot.tick, s.tick) >= 0.0);
+            match before {
+                Some(i) if self.snapshots[i].tick == snapshot.tick => self.snapshots[i] = snapshot,
+                Some(i) => self.snapshots.insert(i + 1, snapshot),
+                None => self.snapshots.push_front(snapshot),
             }
-            if self.interp < interpolation {
-                self.interp = interpolation;
-                self.interp_x = self.last_x + (self.next_x - self.last_x) * self.interp;
-                self.interp_y = self.last_y + (self.next_y - self.last_y) * self.interp;
-                self.interp_z = self.last_z + (self.next_z - self.last_z) * self.interp;
+            if self.snapshots.len() > CAPACITY {
+                self.snapshots.pop_front();
+            }
+        }
+
+        /// Drops snapshots that are too old to be drawn again
+        pub fn prune(&mut self, now: Tick, age: f32) {
+            while self.snapshots.len() > 1 && ticks_since(now, self.snapshots[1].tick) >= age {
+                self.snapshots.pop_front();
             }
         }
-    }
 
-    #[derive(Default, Component)]
-    pub struct InterpRot {
-        interp: f32,
+        /// The state `age` ticks before `now`
+        pub fn sample(&self, now: Tick, age: f32) -> Sample {
+            let newest = self.snapshots.back().expect("never empty. qed");
+            let newest_age = ticks_since(now, newest.tick);
+            if age <= newest_age {
+                let ticks = (newest_age - age).min(MAX_EXTRAPOLATION);
+                let linvel = newest.linvel.unwrap_or_default();
+                return Sample {
+                    translation: newest.translation
+                        + linvel * ticks * protocol::TICK_INTERVAL.as_secs_f32(),
+                    rotation: newest.rotation,
+                    extrapolated: linvel != Vec3::ZERO && ticks > 0.0,
+                };
+            }
 
-        pub interp_x: f32,
-        pub interp_y: f32,
-        pub interp_z: f32,
-        pub interp_w: f32,
+            let oldest = self.snapshots.front().expect("never empty. qed");
+            if age >= ticks_since(now, oldest.tick) {
+                return Sample {
+                    translation: oldest.translation,
+                    rotation: oldest.rotation,
+                    extrapolated: false,
+                };
+            }
 
-        last_x: f32,
-        last_y: f32,
-        last_z: f32,
-        last_w: f32,
+            let (before, after) = self
+                .snapshots
+                .iter()
+                .zip(self.snapshots.iter().skip(1))
+                .find(|(_, after)| ticks_since(now, after.tick) <= age)
+                .expect("age is between the oldest and the newest snapshot. qed");
+            let before_age = ticks_since(now, before.tick);
+            let after_age = ticks_since(now, after.tick);
+            let t = (before_age - age) / (before_age - after_age);
+            Sample {
+                translation: before.translation.lerp(after.translation, t),
+                // glam's slerp takes the short way around
+                rotation: before.rotation.slerp(after.rotation, t),
+                extrapolated: false,
+            }
+        }
+    }
 
-        pub next_x: f32,
-        pub next_y: f32,
-        pub next_z: f32,
-        pub next_w: f32,
+    /// All in ticks.  Lateness is measured against naia's estimate of the server tick, so it
+    /// includes the latency as well as any offset in that estimate.
+    #[derive(Resource, Default, Debug)]
+    pub struct SnapshotStats {
+        /// Average ticks between a snapshot being sent and arriving
+        pub lateness: f32,
+        /// Average deviation from `lateness`
+        pub jitter: f32,
+        /// How far in the past confirmed entities are drawn, `lateness` plus the interp delay
+        pub delay: f32,
+        /// Entity frames drawn past the newest snapshot
+        pub extrapolated: u64,
+        arrivals: u64,
     }
 
-    impl InterpRot {
-        pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
-            Self {
-                interp: 0.0,
-
-                interp_x: x,
-                interp_y: y,
-                interp_z: z,
-                interp_w: w,
-
-                last_x: x,
-                last_y: y,
-                last_z: z,
-                last_w: w,
-
-                next_x: x,
-                next_y: y,
-                next_z: z,
-                next_w: w,
+    impl SnapshotStats {
+        fn arrived(&mut self, lateness: f32) {
+            if self.arrivals == 0 {
+                self.lateness = lateness;
+            } else {
+                let deviation = (lateness - self.lateness).abs();
+                self.lateness += (lateness - self.lateness) * SMOOTHING;
+                self.jitter += (deviation - self.jitter) * SMOOTHING;
             }
+            self.arrivals += 1;
         }
 
-        pub(crate) fn next(&mut self, next_x: f32, next_y: f32, next_z: f32, next_w: f32) {
-            self.interp = 0.0;
-            self.last_x = self.next_x;
-            self.last_y = self.next_y;
-            self.last_z = self.next_z;
-            self.last_w = self.next_w;
-
-            self.interp_x = self.next_x;
-            self.interp_y = self.next_y;
-            self.interp_z = self.next_z;
-            self.interp_w = self.next_w;
-
-            self.next_x = next_x;
-            self.next_y = next_y;
-            self.next_z = next_z;
-            // self.next_w = next_w;
+        /// The server tick confirmed entities are drawn at
+        pub fn seen_tick(&self, now: Tick, interpolation: f32) -> Tick {
+            now.wrapping_sub((self.delay - interpolation).round() as i16 as u16)
         }
+    }
 
-        pub(crate) fn interpolate(&mut self, interpolation: f32) {
-            if self.interp >= 1.0 || interpolation == 0.0 {
-                return;
-            }
-            if self.interp < interpolation {
-                self.interp = interpolation;
-
-                // let lerped = slerp(
-                //     Quat::from_xyzw(self.last_x, self.last_y, self.last_z, self.last_w),
-                //     Quat::from_xyzw(self.next_x, self.next_y, self.next_z, self.next_w),
-                //     self.interp,
-                // );
-                //
-                // self.interp_x = lerped.x;
-                // self.interp_y = lerped.y;
-                // self.interp_z = lerped.z;
-                // self.interp_w = lerped.w;
-
-                self.interp_x = self.last_x + (self.next_x - self.last_x) * self.interp;
-                self.interp_y = self.last_y + (self.next_y - self.last_y) * self.interp;
-                self.interp_z = self.last_z + (self.next_z - self.last_z) * self.interp;
-                self.interp_w = self.last_w + (self.next_w - self.last_w) * self.interp;
+    pub fn receive(
+        client: Client,
+        mut stats: ResMut<SnapshotStats>,
+        mut event_reader: EventReader<UpdateComponentEvents>,
+        mut query: Query<(&RepPhysics, &mut SnapshotBuffer)>,
+    ) {
+        let (Some(now), Some(interpolation)) =
+            (client.server_tick(), client.server_interpolation())
+        else {
+            return;
+        };
+
+        for events in event_reader.iter() {
+            for (tick, entity) in events.read::<RepPhysics>() {
+                let Ok((rep_physics, mut buffer)) = query.get_mut(entity) else {
+                    continue;
+                };
+                stats.arrived(ticks_since(now, tick) + interpolation);
+                buffer.push(Snapshot::new(tick, rep_physics));
             }
         }
     }
 
-    // fn lerp(a: Quat, b: Quat, s: f32) -> Quat {
-    //     use std::ops::{Add, Mul, Sub};
-    //     let start = a;
-    //     let dot = start.dot(b);
-    //     let bias = if dot >= 0.0 { 1.0 } else { -1.0 };
-    //     let interpolated = a.add(b.mul(bias).sub(start).mul(s));
-    //     interpolated
-    // }
-    //
-    // pub fn slerp(a: Quat, mut b: Quat, s: f32) -> Quat {
-    //     use std::ops::{Add, Mul};
-    //     const DOT_THRESHOLD: f32 = 0.9995;
-    //
-    //     // Note that a rotation can be represented by two quaternions: `q` and
-    //     // `-q`. The slerp path between `q` and `end` will be different from the
-    //     // path between `-q` and `end`. One path will take the long way around and
-    //     // one will take the short way. In order to correct for this, the `dot`
-    //     // product between `self` and `end` should be positive. If the `dot`
-    //     // product is negative, slerp between `self` and `-end`.
-    //     let mut dot = a.dot(b);
-    //     if dot < 0.0 {
-    //         b = -b;
-    //         dot = -dot;
-    //     }
-    //
-    //     if dot > DOT_THRESHOLD {
-    //         // assumes lerp returns a normalized quaternion
-    //         lerp(a, b, s)
-    //     } else {
-    //         let theta = dot.acos_approx();
-    //
-    //         let scale1 = (theta * (1.0 - s)).sin();
-    //         let scale2 = (theta * s).sin();
-    //         let theta_sin = theta.sin();
-    //
-    //         a.mul(scale1).add(b.mul(scale2)).mul(theta_sin.recip())
-    //     }
-    // }
-    //
-    // pub(crate) trait FloatEx {
-    //     /// Returns a very close approximation of `self.clamp(-1.0, 1.0).acos()`.
-    //     fn acos_approx(self) -> Self;
-    // }
-    //
-    // impl FloatEx for f32 {
-    //     #[inline(always)]
-    //     fn acos_approx(self) -> Self {
-    //         // Based on https://github.com/microsoft/DirectXMath `XMScalarAcos`
-    //         // Clamp input to [-1,1].
-    //         let nonnegative = self >= 0.0;
-    //         let x = self.abs();
-    //         let mut omx = 1.0 - x;
-    //         if omx < 0.0 {
-    //             omx = 0.0;
-    //         }
-    //         let root = omx.sqrt();
-    //
-    //         // 7-degree minimax approximation
-    //         #[allow(clippy::approx_constant)]
-    //         let mut result = ((((((-0.001_262_491_1 * x + 0.006_670_09) * x - 0.017_088_126)
-    //             * x
-    //             + 0.030_891_88)
-    //             * x
-    //             - 0.050_174_303)
-    //             * x
-    //             + 0.088_978_99)
-    //             * x
-    //             - 0.214_598_8)
-    //             * x
-    //             + 1.570_796_3;
-    //         result *= root;
-    //
-    //         // acos(x) = pi - acos(-x) when x < 0
-    //         if nonnegative {
-    //             result
-    //         } else {
-    //             std::f32::consts::PI - result
-    //         }
-    //     }
-    // }
+    pub fn update_delay(settings: Res<ClientSettings>, mut stats: ResMut<SnapshotStats>) {
+        let interp_delay =
+            settings.interp_delay.as_secs_f32() / protocol::TICK_INTERVAL.as_secs_f32();
+        stats.delay = stats.lateness + interp_delay;
+    }
+
+    pub fn log_stats(time: Res<Time>, stats: Res<SnapshotStats>, mut since_last: Local<f32>) {
+        *since_last += time.delta_seconds();
+        if *since_last >= LOG_INTERVAL {
+            *since_last = 0.0;
+            info!(
+                "snapshots: delay {:.1} ticks, lateness {:.1}, jitter {:.2}, {} extrapolated",
+                stats.delay, stats.lateness, stats.jitter, stats.extrapolated
+            );
+        }
+    }
 }
 
 mod events {
-    use super::components::{Confirmed, InterpPos, InterpRot, Predicted};
-    use super::{Global, OwnedEntity};
+    use super::components::{Confirmed, Predicted};
+    use super::snapshot::{Snapshot, SnapshotBuffer};
+    use super::{goalie::YOSHI_FACING, reconnect::Reconnect, ClientSettings, Global, OwnedEntity};
     use crate::AppState;
-    use core::{components::Ball, constants::*};
+    use core::{components::Ball, constants::*, rules::KickModel};
 
     use protocol::{
         channels::{EntityAssignmentChannel, GameStateChannel, PlayerCommandChannel},
         components::{EntityKind, EntityKindValue, Player, RepPhysics, UpdateWith},
-        messages::{EntityAssignment, EventKind, KeyCommand, PlayerEvent, TotalScoreState},
+        messages::{
+            Announcement, EntityAssignment, EventKind, KeyCommand, PlayerEvent, PlayerStatsState,
+            Rejected, ServerInfo, Session, TotalScoreState,
+        },
         primitives::PlayColor,
     };
 
@@ -790,16 +940,23 @@ mod events {
     use naia_bevy_client::{
         events::{
             ClientTickEvent, ConnectEvent, DespawnEntityEvent, DisconnectEvent,
-            InsertComponentEvents, MessageEvents, RejectEvent, RemoveComponentEvents,
-            SpawnEntityEvent, UpdateComponentEvents,
+            InsertComponentEvents, MessageEvents, RemoveComponentEvents, SpawnEntityEvent,
+            UpdateComponentEvents,
         },
-        Client, CommandsExt,
+        sequence_greater_than, Client, CommandsExt, Tick,
     };
 
+    /// How far the predicted ball may drift from the confirmed ball before it is rolled back
+    const PREDICTION_TOLERANCE: f32 = 0.25;
+    /// How far a confirmed ball may be from `BALL_START` and still be on the spot
+    const SPOT_TOLERANCE: f32 = 0.01;
+
     pub fn connect_events(
-        // mut commands: Commands,
-        global: Res<Global>,
+        mut commands: Commands,
+        mut global: ResMut<Global>,
         client: Client,
+        asset_server: Res<AssetServer>,
+        mut reconnect: ResMut<Reconnect>,
         mut next_state: ResMut<NextState<AppState>>,
         mut event_reader: EventReader<ConnectEvent>,
     ) {
@@ -808,6 +965,8 @@ mod events {
             panic!("Shouldn't happen");
         };
             info!("Client connected to: {}", server_address);
+            reconnect.connected();
+            show_notice(&mut commands, &mut global, &asset_server, "");
 
             // // Create entity for Client-authoritative Cursor
             //
@@ -843,15 +1002,67 @@ mod events {
         }
     }
 
-    pub fn reject_events(mut event_reader: EventReader<RejectEvent>) {
-        for _ in event_reader.iter() {
-            info!("Client rejected from connecting to Server");
+    /// Centered message over the game, replaces the previous one.  An empty text just clears it.
+    fn show_notice(
+        commands: &mut Commands,
+        global: &mut Global,
+        asset_server: &AssetServer,
+        text: &str,
+    ) {
+        if let Some(entity) = global.notice_entity.take() {
+            commands.entity(entity).despawn_recursive();
         }
+        if text.is_empty() {
+            return;
+        }
+        let notice = commands
+            .spawn(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(20.0),
+                        right: Val::Px(20.0),
+                        top: Val::Percent(40.0),
+                        ..default()
+                    },
+                    ..default()
+                },
+                text: Text::from_section(
+                    text,
+                    TextStyle {
+                        font: asset_server.load("fonts/color-mario.ttf"),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_alignment(TextAlignment::Center),
+                ..default()
+            })
+            .id();
+        global.notice_entity = Some(notice);
     }
 
-    pub fn disconnect_events(mut event_reader: EventReader<DisconnectEvent>) {
+    /// naia despawns the replicated entities, our predicted copy of the ball goes with them
+    pub fn disconnect_events(
+        mut commands: Commands,
+        mut global: ResMut<Global>,
+        asset_server: Res<AssetServer>,
+        mut reconnect: ResMut<Reconnect>,
+        mut event_reader: EventReader<DisconnectEvent>,
+    ) {
         for _ in event_reader.iter() {
             info!("Client disconnected from Server");
+            if let Some(owned_entity) = global.owned_entity.take() {
+                commands.entity(owned_entity.predicted).despawn();
+            }
+            global.last_kicked = None;
+            global.queued_command = None;
+            global.command_history = Default::default();
+
+            reconnect.disconnected();
+            if reconnect.enabled {
+                show_notice(&mut commands, &mut global, &asset_server, "Reconnecting...");
+            }
         }
     }
 
@@ -860,8 +1071,10 @@ mod events {
         mut global: ResMut<Global>,
         mut materials: ResMut<Assets<StandardMaterial>>,
         audio: Res<Audio>,
+        asset_server: Res<AssetServer>,
 
         mut commands: Commands,
+        mut reconnect: ResMut<Reconnect>,
         mut event_reader: EventReader<MessageEvents>,
 
         ball_query: Query<(&RepPhysics, &Handle<StandardMaterial>)>,
@@ -882,8 +1095,9 @@ mod events {
                 handle_player_event(&mut global, &client, &audio, &mut text_query, message);
             }
             for message in events.read::<GameStateChannel, TotalScoreState>() {
-                global.total_pink += message.pink;
-                global.total_blue += message.blue;
+                // sent on connect and when the server operator adjusts the tally
+                global.total_pink = message.pink;
+                global.total_blue = message.blue;
                 text_query
                     .get_mut(global.total_pink_entity.unwrap())
                     .unwrap()
@@ -900,6 +1114,40 @@ mod events {
                     message.blue,
                 );
             }
+            for message in events.read::<GameStateChannel, ServerInfo>() {
+                info!(
+                    "server protocol {} (ours {}), features {:?}",
+                    message.version,
+                    protocol::PROTOCOL_VERSION,
+                    message.features
+                );
+                global.server_features = message.features;
+            }
+            for message in events.read::<GameStateChannel, Rejected>() {
+                info!("rejected by the server: {:?}", message.reason);
+                reconnect.enabled = false;
+                show_notice(
+                    &mut commands,
+                    &mut global,
+                    &asset_server,
+                    message.reason.message(),
+                );
+            }
+            for message in events.read::<GameStateChannel, Session>() {
+                reconnect.session = Some(message.token);
+            }
+            for message in events.read::<GameStateChannel, Announcement>() {
+                info!("announcement: {}", message.text);
+                show_notice(&mut commands, &mut global, &asset_server, &message.text);
+            }
+            for message in events.read::<GameStateChannel, PlayerStatsState>() {
+                global.my_stats = message.stats;
+                text_query
+                    .get_mut(global.own_score_entity.unwrap())
+                    .unwrap()
+                    .sections[0]
+                    .value = own_score_text(&global);
+            }
         }
     }
 
@@ -918,52 +1166,49 @@ mod events {
 
             // Here we create a local copy of the Player entity, to use for client-side prediction
             if let Ok((rep_physics, mat_handle)) = ball_query.get(entity) {
-                //change the owned_confirmed entity (server side) to a transparent red
-                let confirmed_ball_mat = materials.get_mut(mat_handle).unwrap();
-                confirmed_ball_mat.base_color.set_a(1.0);
-
-                // // take and give this texture to the prediction ball.  All balls
-                // // are spawned with the default texture
-                // let confirmed_texture = confirmed_ball_mat.base_color_texture.take();
-                // // set the confirmed ball to red with transparency
-                // confirmed_ball_mat.base_color = Color::rgba(0.7, 0.2, 0.1, 0.4);
-                // confirmed_ball_mat.unlit = true;
-
-                //add physics ball for clicking, eventually use it for prediction?
+                // The predicted ball is the one the player sees and kicks.  The confirmed ball is
+                // hidden and only used to correct the prediction.
+                let confirmed_texture = materials
+                    .get(mat_handle)
+                    .and_then(|mat| mat.base_color_texture.clone());
+                commands.entity(entity).insert(Visibility::Hidden);
+
                 let mut prediction_transform = Transform::default();
                 prediction_transform.update_with(rep_physics);
+                let mut prediction_velocity = Velocity::zero();
+                prediction_velocity.update_with(rep_physics);
                 let prediction_entity = commands
                     .entity(entity)
                     .duplicate()
                     .insert((
                         PbrBundle {
                             mesh: global.ball_mesh.clone(),
-                            material: materials.add(Color::rgba(0.7, 0.2, 0.1, 0.0).into()),
-                            // material: materials.add(StandardMaterial {
-                            //     base_color: Color::rgb(1.0, 1.0, 1.0),
-                            //     base_color_texture: confirmed_texture,
-                            //     alpha_mode: AlphaMode::Blend,
-                            //     ..default()
-                            // }),
+                            material: materials.add(StandardMaterial {
+                                base_color: Color::rgb(1.0, 1.0, 1.0),
+                                base_color_texture: confirmed_texture,
+                                alpha_mode: AlphaMode::Blend,
+                                ..default()
+                            }),
                             transform: prediction_transform,
                             ..default()
                         },
+                        Ball::default(),
                         RigidBody::Dynamic,
                         Collider::ball(BALL_RADIUS),
-                        // ColliderMassProperties::Mass(BALL_MASS),
-                        // Velocity::zero(),
-                        // Friction::new(5.0),
-                        // ExternalForce::default(),
-                        // ExternalImpulse::default(),
-                        // GravityScale::default(),
-                        // Damping {
-                        //     linear_damping: 1.0,
-                        //     angular_damping: 2.0,
-                        // },
-                        // Restitution {
-                        //     coefficient: 1.0,
-                        //     combine_rule: CoefficientCombineRule::Average,
-                        // },
+                        ColliderMassProperties::Mass(BALL_MASS),
+                        prediction_velocity,
+                        Friction::new(5.0),
+                        ExternalForce::default(),
+                        ExternalImpulse::default(),
+                        GravityScale::default(),
+                        Damping {
+                            linear_damping: 1.0,
+                            angular_damping: 2.0,
+                        },
+                        Restitution {
+                            coefficient: 1.0,
+                            combine_rule: CoefficientCombineRule::Average,
+                        },
                         Sleeping::default(),
                         Predicted,
                     ))
@@ -986,6 +1231,15 @@ mod events {
         }
     }
 
+    /// Goals this session and lifetime accuracy
+    fn own_score_text(global: &Global) -> String {
+        format!(
+            "{} ({:.0}%)",
+            global.my_score,
+            global.my_stats.accuracy() * 100.0
+        )
+    }
+
     fn handle_player_event(
         global: &mut ResMut<Global>,
         client: &Client,
@@ -1004,7 +1258,7 @@ mod events {
                             .get_mut(global.own_score_entity.unwrap())
                             .unwrap()
                             .sections[0]
-                            .value = global.my_score.to_string();
+                            .value = own_score_text(global);
                     }
                 }
                 global.total_blue += 1;
@@ -1025,7 +1279,7 @@ mod events {
                             .get_mut(global.own_score_entity.unwrap())
                             .unwrap()
                             .sections[0]
-                            .value = global.my_score.to_string();
+                            .value = own_score_text(global);
                     }
                 }
                 global.total_pink += 1;
@@ -1039,6 +1293,7 @@ mod events {
             //do nothing for now
             EventKind::ScoreSnapshot(_n) => {}
             EventKind::Kicked => {
+                global.last_kicked = message.entity.get(client);
                 audio.play(global.kick_sound.clone());
             }
             EventKind::DeniedGoalie => {
@@ -1069,7 +1324,9 @@ mod events {
     pub struct Goalie;
 
     pub fn insert_component_events(
+        client: Client,
         global: Res<Global>,
+        settings: Res<ClientSettings>,
         mut materials: ResMut<Assets<StandardMaterial>>,
 
         mut commands: Commands,
@@ -1082,8 +1339,13 @@ mod events {
         for events in event_reader.iter() {
             for entity in events.read::<Player>() {
                 let player = player_query.get(entity).unwrap();
-                let rep_physics = rep_physics_query.get(entity).unwrap();
+                let translation = rep_physics_query.get(entity).unwrap().translation();
 
+                let alpha = if settings.spectate || settings.goalie {
+                    1.0
+                } else {
+                    0.2
+                };
                 let texture = if let PlayColor::Blue = *player.color {
                     global.ball_texture.clone()
                 } else {
@@ -1094,16 +1356,14 @@ mod events {
                     PbrBundle {
                         mesh: global.ball_mesh.clone(),
                         material: materials.add(StandardMaterial {
-                            base_color: Color::rgba(1.0, 1.0, 1.0, 0.2),
+                            // other players' balls are see-through, except on the big screen and
+                            // for the goalie
+                            base_color: Color::rgba(1.0, 1.0, 1.0, alpha),
                             base_color_texture: Some(texture),
                             alpha_mode: AlphaMode::Blend,
                             ..default()
                         }),
-                        transform: Transform::from_xyz(
-                            *rep_physics.translation_x,
-                            *rep_physics.translation_y,
-                            *rep_physics.translation_z,
-                        ),
+                        transform: Transform::from_translation(translation),
                         ..default()
                     },
                     NotShadowReceiver,
@@ -1113,6 +1373,8 @@ mod events {
             for entity in events.read::<RepPhysics>() {
                 let kind = kind_query.get(entity).unwrap();
                 let rep_physics = rep_physics_query.get(entity).unwrap();
+                let translation = rep_physics.translation();
+                let snapshot = Snapshot::new(client.server_tick().unwrap_or_default(), rep_physics);
 
                 log::info!("entity: {:?}", *kind.value);
                 match *kind.value {
@@ -1122,23 +1384,13 @@ mod events {
                             Transform {
                                 translation: Vec3::new(
                                     // 0.0, 0.0, 0.0,
-                                    *rep_physics.translation_x,
+                                    translation.x,
                                     GOALIE_START.y,
                                     GOALIE_START.z,
                                 ),
                                 ..Default::default()
                             },
-                            InterpPos::new(
-                                *rep_physics.translation_x,
-                                *rep_physics.translation_y,
-                                *rep_physics.translation_z,
-                            ),
-                            InterpRot::new(
-                                *rep_physics.rotation_x,
-                                *rep_physics.rotation_y,
-                                *rep_physics.rotation_z,
-                                *rep_physics.rotation_w,
-                            ),
+                            SnapshotBuffer::new(snapshot),
                             Confirmed,
                             GoalieRemote,
                         ));
@@ -1147,11 +1399,11 @@ mod events {
                                 scene: global.goalie_scene.clone(),
                                 transform: Transform {
                                     translation: Vec3::new(
-                                        *rep_physics.translation_x,
+                                        translation.x,
                                         GROUND_HEIGHT,
                                         GOALIE_START.z,
                                     ),
-                                    rotation: Quat::from_rotation_y(4.71239),
+                                    rotation: Quat::from_rotation_y(YOSHI_FACING),
                                     ..Default::default()
                                 },
                                 ..Default::default()
@@ -1163,17 +1415,7 @@ mod events {
                         commands.entity(entity).insert((
                             Name::new("Ball"),
                             Ball::default(),
-                            InterpPos::new(
-                                *rep_physics.translation_x,
-                                *rep_physics.translation_y,
-                                *rep_physics.translation_z,
-                            ),
-                            InterpRot::new(
-                                *rep_physics.rotation_x,
-                                *rep_physics.rotation_y,
-                                *rep_physics.rotation_z,
-                                *rep_physics.rotation_w,
-                            ),
+                            SnapshotBuffer::new(snapshot),
                             Confirmed,
                         ));
                     }
@@ -1183,54 +1425,133 @@ mod events {
     }
 
     pub fn update_component_events(
-        // mut global: ResMut<Global>,
-        mut _event_reader: EventReader<UpdateComponentEvents>,
-        // mut position_query: Query<&mut Position>,
+        mut global: ResMut<Global>,
+        kick_model: Res<KickModel>,
+        mut event_reader: EventReader<UpdateComponentEvents>,
+        confirmed_query: Query<&RepPhysics, With<Confirmed>>,
+        mut predicted_query: Query<
+            (
+                &mut Transform,
+                &mut Velocity,
+                &mut Ball,
+                &mut ExternalImpulse,
+                &mut ExternalForce,
+            ),
+            With<Predicted>,
+        >,
     ) {
-        // When we receive a new Position update for the Player's Entity,
+        // When we receive a new RepPhysics update for the Player's Entity,
         // we must ensure the Client-side Prediction also remains in-sync
         // So we roll the Prediction back to the authoritative Server state
         // and then execute all Player Commands since that tick, using the CommandHistory helper struct
-        // if let Some(owned_entity) = &global.owned_entity {
-        //     let mut latest_tick: Option<Tick> = None;
-        //     let server_entity = owned_entity.confirmed;
-        //     let client_entity = owned_entity.predicted;
-        //
-        //     for events in event_reader.iter() {
-        //         for (server_tick, updated_entity) in events.read::<Position>() {
-        //             // If entity is owned
-        //             if updated_entity == server_entity {
-        //                 if let Some(last_tick) = &mut latest_tick {
-        //                     if sequence_greater_than(server_tick, *last_tick) {
-        //                         *last_tick = server_tick;
-        //                     }
-        //                 } else {
-        //                     latest_tick = Some(server_tick);
-        //                 }
-        //             }
-        //         }
-        //     }
-        //
-        //     if let Some(server_tick) = latest_tick {
-        //         if let Ok([server_position, mut client_position]) =
-        //             position_query.get_many_mut([server_entity, client_entity])
-        //         {
-        //             // Set to authoritative state
-        //             client_position.mirror(&*server_position);
-        //
-        //             // Replay all stored commands
-        //
-        //             // TODO: why is it necessary to subtract 1 Tick here?
-        //             // it's not like this in the Macroquad demo
-        //             let modified_server_tick = server_tick.wrapping_sub(1);
-        //
-        //             let replay_commands = global.command_history.replays(&modified_server_tick);
-        //             for (_command_tick, command) in replay_commands {
-        //                 process_command(&command, &mut client_position);
-        //             }
-        //         }
-        //     }
-        // }
+        let Some(owned_entity) = global.owned_entity.clone() else {
+            return;
+        };
+
+        let mut latest_tick: Option<Tick> = None;
+        for events in event_reader.iter() {
+            for (server_tick, updated_entity) in events.read::<RepPhysics>() {
+                // If entity is owned
+                if updated_entity == owned_entity.confirmed {
+                    if let Some(last_tick) = &mut latest_tick {
+                        if sequence_greater_than(server_tick, *last_tick) {
+                            *last_tick = server_tick;
+                        }
+                    } else {
+                        latest_tick = Some(server_tick);
+                    }
+                }
+            }
+        }
+
+        let Some(server_tick) = latest_tick else {
+            return;
+        };
+
+        let (Ok(rep_physics), Ok((mut transform, mut velocity, mut ball, mut ext_i, mut ext_f))) = (
+            confirmed_query.get(owned_entity.confirmed),
+            predicted_query.get_mut(owned_entity.predicted),
+        ) else {
+            return;
+        };
+
+        // TODO: why is it necessary to subtract 1 Tick here?
+        // it's not like this in the Macroquad demo
+        let modified_server_tick = server_tick.wrapping_sub(1);
+        let replay_commands = global.command_history.replays(&modified_server_tick);
+
+        // A kick the server hasn't processed yet means the prediction is ahead on purpose
+        let pending_shot = replay_commands
+            .iter()
+            .any(|(_, command)| command.shoot.is_some());
+        if pending_shot && ball.shot {
+            return;
+        }
+
+        let mut confirmed_transform = Transform::default();
+        confirmed_transform.update_with(rep_physics);
+        let mut confirmed_velocity = Velocity::default();
+        confirmed_velocity.update_with(rep_physics);
+        let confirmed_shot = is_shot(&confirmed_transform, &confirmed_velocity);
+        if confirmed_shot == ball.shot
+            && transform
+                .translation
+                .distance(confirmed_transform.translation)
+                < PREDICTION_TOLERANCE
+        {
+            return;
+        }
+
+        // Set to authoritative state
+        transform.update_with(rep_physics);
+        velocity.update_with(rep_physics);
+        *ext_i = ExternalImpulse::default();
+        *ext_f = ExternalForce::default();
+        if !confirmed_shot {
+            *ball = Ball::default();
+        } else if !ball.shot {
+            *ball = Ball {
+                shot: true,
+                ..default()
+            };
+        }
+
+        // Replay all stored commands
+        for (_command_tick, command) in replay_commands {
+            process_command(&kick_model, &command, &transform, &mut ball, &mut ext_i);
+        }
+    }
+
+    /// Whether a confirmed ball is in a shot.  Balls only leave the spot when kicked and the
+    /// server resets them onto it with zero velocity, so this only has to allow for quantization.
+    fn is_shot(transform: &Transform, velocity: &Velocity) -> bool {
+        let offset = transform.translation - BALL_START;
+        let moving = velocity.linvel.x != 0.0 || velocity.linvel.z != 0.0;
+        moving || offset.x.abs() > SPOT_TOLERANCE || offset.z.abs() > SPOT_TOLERANCE
+    }
+
+    /// Local version of `server::process_ball_command` for the predicted ball
+    fn process_command(
+        kick_model: &KickModel,
+        command: &KeyCommand,
+        transform: &Transform,
+        ball: &mut Ball,
+        ext_i: &mut ExternalImpulse,
+    ) {
+        if command.reset && ball.shot && !ball.scored {
+            ball.force_reset = true;
+            return;
+        }
+
+        if let Some((ray_normal, ray_point)) = &command.shoot {
+            kick_model.kick(
+                ray_normal.clone().into(),
+                ray_point.clone().into(),
+                transform,
+                ball,
+                ext_i,
+            );
+        }
     }
 
     pub fn remove_component_events(mut event_reader: EventReader<RemoveComponentEvents>) {
@@ -1244,10 +1565,11 @@ mod events {
     pub fn tick_events(
         mut client: Client,
         mut global: ResMut<Global>,
+        kick_model: Res<KickModel>,
         mut tick_reader: EventReader<ClientTickEvent>,
-        // mut position_query: Query<&mut Position>,
+        mut predicted_query: Query<(&Transform, &mut Ball, &mut ExternalImpulse), With<Predicted>>,
     ) {
-        let Some(_predicted_entity) = global
+        let Some(predicted_entity) = global
             .owned_entity
             .as_ref()
             .map(|owned_entity| owned_entity.predicted) else {
@@ -1274,17 +1596,21 @@ mod events {
                 &command,
             );
 
-            //#TODO client-side prediction command isn't handled yet
-            // we want to 'process_ball_commmand' on the predicted entity
+            // Apply command to the predicted entity right away so the kick feels instant
+            if let Ok((transform, mut ball, mut ext_i)) = predicted_query.get_mut(predicted_entity)
+            {
+                process_command(&kick_model, &command, transform, &mut ball, &mut ext_i);
+            }
         }
     }
 }
 
 mod input {
-    use super::components::Confirmed;
+    use super::components::Predicted;
+    use super::snapshot::SnapshotStats;
     use super::Global;
     use core::constants::*;
-    use protocol::messages::KeyCommand;
+    use protocol::{messages::KeyCommand, primitives::ServerFeature};
 
     use bevy::{prelude::*, render::camera::RenderTarget, window::PrimaryWindow};
     use bevy_rapier3d::prelude::*;
@@ -1327,7 +1653,8 @@ mod input {
         keyboard_input: Res<Input<KeyCode>>,
         mouse_buttons: ResMut<Input<MouseButton>>,
         touches: Res<Touches>,
-        ball_query: Query<&Transform, With<Confirmed>>,
+        snapshot_stats: Res<SnapshotStats>,
+        ball_query: Query<&Transform, With<Predicted>>,
 
         camera_query: Query<(&Camera, &Transform, &GlobalTransform)>,
         window: Query<&Window, With<PrimaryWindow>>,
@@ -1337,15 +1664,14 @@ mod input {
             return;
         };
 
-        let Ok(ball_transform) = ball_query.get(owned_entity.confirmed) else {
+        let Ok(ball_transform) = ball_query.get(owned_entity.predicted) else {
             return;
         };
 
         //#HACK only let the ball get kicked if it has already settled down from the spawn
-        //point.  When prediction is implemented, We use the local predicted ball and not the
-        //confirmed ball because of lag compensation.  IE, we expect the confirmed ball to be
-        //already dropped by the time we actually see the updates at the client.  The predicted
-        //ball is spawned after the ball entity assignment.  For now use the Confirmed Entity
+        //point.  We use the local predicted ball and not the confirmed ball because of lag
+        //compensation.  IE, we expect the confirmed ball to be already dropped by the time we
+        //actually see the updates at the client.
         let can_shoot = ball_transform.translation.y < 0.01;
 
         let reset = keyboard_input.pressed(KeyCode::Q);
@@ -1389,11 +1715,25 @@ mod input {
             _ => None,
         };
 
+        // The goalie is drawn `SnapshotStats::delay` ticks in the past
+        let seen_tick = if global
+            .server_features
+            .contains(&ServerFeature::LagCompensation)
+        {
+            client
+                .server_tick()
+                .zip(client.server_interpolation())
+                .map(|(now, interpolation)| snapshot_stats.seen_tick(now, interpolation))
+        } else {
+            None
+        };
+
         if let Some(command) = &mut global.queued_command {
             command.reset = reset;
             command.shoot = shoot;
+            command.seen_tick = seen_tick;
         } else if let Some(owned_entity) = &global.owned_entity {
-            let mut key_command = KeyCommand::new(reset, shoot);
+            let mut key_command = KeyCommand::new(reset, shoot, seen_tick);
             key_command.entity.set(&client, &owned_entity.confirmed);
             global.queued_command = Some(key_command);
         }
@@ -1467,8 +1807,308 @@ mod input {
     }
 }
 
+/// Connects again after losing the server, backing off between attempts.  The `Session` token
+/// from the server gets our ball back if we make it within the server's grace period, otherwise we
+/// join as a new player.
+pub mod reconnect {
+    use super::ClientSettings;
+    use protocol::messages::Auth;
+
+    use bevy::prelude::*;
+    use naia_bevy_client::{transport::webrtc, Client};
+
+    /// Seconds before the first attempt, doubled after every failed one
+    const FIRST_WAIT: f32 = 1.0;
+    const MAX_WAIT: f32 = 16.0;
+
+    #[derive(Resource)]
+    pub struct Reconnect {
+        /// Name and color, `None` when spectating
+        player: Option<(String, String)>,
+        /// From `Session`, sent as `Auth::resume`
+        pub session: Option<String>,
+        /// Seconds until the next attempt, `None` while connected
+        wait: Option<f32>,
+        attempts: u32,
+        /// Cleared when the server turns us away, trying again wouldn't help
+        pub enabled: bool,
+    }
+
+    impl Reconnect {
+        pub fn new(player: Option<(String, String)>) -> Self {
+            Self {
+                player,
+                session: None,
+                wait: None,
+                attempts: 0,
+                enabled: true,
+            }
+        }
+
+        pub fn auth(&self, settings: &ClientSettings) -> Auth {
+            let auth = match &self.player {
+                Some(player) => Auth::from(player.clone()),
+                None if settings.goalie => Auth::goalie(),
+                None => Auth::spectator(),
+            };
+            auth.with_token(settings.token.clone())
+                .with_room(settings.room.clone())
+                .with_resume(self.session.clone())
+        }
+
+        pub fn connected(&mut self) {
+            self.wait = None;
+            self.attempts = 0;
+        }
+
+        pub fn disconnected(&mut self) {
+            if self.enabled {
+                self.wait = Some(backoff(self.attempts));
+            }
+        }
+    }
+
+    fn backoff(attempts: u32) -> f32 {
+        (FIRST_WAIT * 2f32.powi(attempts as i32)).min(MAX_WAIT)
+    }
+
+    pub fn retry(
+        time: Res<Time>,
+        settings: Res<ClientSettings>,
+        mut client: Client,
+        mut reconnect: ResMut<Reconnect>,
+    ) {
+        let Some(wait) = reconnect.wait.as_mut() else {
+            return;
+        };
+        *wait -= time.delta_seconds();
+        if *wait > 0.0 {
+            return;
+        }
+
+        reconnect.attempts += 1;
+        reconnect.wait = Some(backoff(reconnect.attempts));
+        // naia keeps retrying the handshake of an attempt that is still going
+        if client.is_connecting() {
+            return;
+        }
+        info!("reconnecting, attempt {}", reconnect.attempts);
+        client.auth(reconnect.auth(&settings));
+        let socket = webrtc::Socket::new(&settings.server_url, client.socket_config());
+        client.connect(socket);
+    }
+}
+
+/// Camera for `ClientSettings::spectate`.  Follows the last kicked ball until it's back at the
+/// kick spot and shows the whole field otherwise.  `Space` switches to a free camera moved with
+/// WASD / the arrow keys and Q/E, and back.
+mod spectator {
+    use super::components::Confirmed;
+    use super::{ClientSettings, Global};
+    use core::constants::*;
+
+    use bevy::prelude::*;
+
+    /// Where the camera sits relative to the followed ball
+    const FOLLOW_OFFSET: Vec3 = Vec3::new(0.0, 2.0, 4.5);
+    /// Higher is snappier
+    const FOLLOW_SPEED: f32 = 3.0;
+    /// m/s
+    const FREE_SPEED: f32 = 10.0;
+
+    pub fn spectating(settings: Res<ClientSettings>) -> bool {
+        settings.spectate
+    }
+
+    pub fn camera(
+        time: Res<Time>,
+        global: Res<Global>,
+        keyboard_input: Res<Input<KeyCode>>,
+        mut free: Local<bool>,
+        ball_query: Query<&Transform, (With<Confirmed>, Without<Camera>)>,
+        mut camera_query: Query<&mut Transform, With<Camera>>,
+    ) {
+        let Ok(mut camera) = camera_query.get_single_mut() else {
+            return;
+        };
+        let dt = time.delta_seconds();
+
+        if keyboard_input.just_pressed(KeyCode::Space) {
+            *free = !*free;
+        }
+        if *free {
+            let axis = |positive: [KeyCode; 2], negative: [KeyCode; 2]| {
+                keyboard_input.any_pressed(positive) as i32 as f32
+                    - keyboard_input.any_pressed(negative) as i32 as f32
+            };
+            let direction = Vec3::new(
+                axis([KeyCode::D, KeyCode::Right], [KeyCode::A, KeyCode::Left]),
+                axis([KeyCode::E, KeyCode::E], [KeyCode::Q, KeyCode::Q]),
+                axis([KeyCode::S, KeyCode::Down], [KeyCode::W, KeyCode::Up]),
+            );
+            camera.translation += direction * FREE_SPEED * dt;
+            return;
+        }
+
+        let followed = global
+            .last_kicked
+            .and_then(|entity| ball_query.get(entity).ok())
+            .filter(|ball| ball.translation.distance(BALL_START) > 0.5);
+        let target = match followed {
+            Some(ball) => Transform::from_translation(ball.translation + FOLLOW_OFFSET)
+                .looking_at(ball.translation, Vec3::Y),
+            None => BIRDS_EYE_CAM.looking_at(BIRDS_EYE_CAM_LOOK, Vec3::Y),
+        };
+        let s = (FOLLOW_SPEED * dt).min(1.0);
+        camera.translation = camera.translation.lerp(target.translation, s);
+        camera.rotation = camera.rotation.slerp(target.rotation, s);
+    }
+}
+
+/// Poses the Yoshi model from the replicated `GoalieState`.  The model has no animation clips, so
+/// its transform gets the same `goalie_ai::GoaliePose` the server gives the goalie's collider.
+/// With `ClientSettings::goalie` the user plays the goalie with `GoalieCommand`s.
+mod goalie {
+    use super::events::{Goalie, GoalieRemote};
+    use super::ClientSettings;
+    use core::{constants::*, goalie_ai::GoalieAction};
+    use protocol::{
+        channels::PlayerCommandChannel,
+        components::{GoalieActionValue, GoalieState},
+        messages::GoalieCommand,
+    };
+
+    use bevy::{prelude::*, window::PrimaryWindow};
+    use naia_bevy_client::{events::ClientTickEvent, Client};
+
+    /// Turns the model towards the kicker
+    pub const YOSHI_FACING: f32 = 4.71239;
+
+    /// The action being played and for how long
+    #[derive(Default)]
+    pub struct Playing {
+        action: GoalieAction,
+        seconds: f32,
+    }
+
+    pub fn animate(
+        time: Res<Time>,
+        mut playing: Local<Playing>,
+        goalie_query: Query<(&Transform, &GoalieState), (With<GoalieRemote>, Without<Goalie>)>,
+        mut yoshi_query: Query<&mut Transform, With<Goalie>>,
+    ) {
+        let (Ok((goalie, state)), Ok(mut yoshi)) =
+            (goalie_query.get_single(), yoshi_query.get_single_mut())
+        else {
+            return;
+        };
+        let action = match *state.action {
+            GoalieActionValue::Stand => GoalieAction::Stand,
+            GoalieActionValue::DiveLeft => GoalieAction::DiveLeft,
+            GoalieActionValue::DiveRight => GoalieAction::DiveRight,
+            GoalieActionValue::Jump => GoalieAction::Jump,
+        };
+        if action == playing.action {
+            playing.seconds += time.delta_seconds();
+        } else {
+            *playing = Playing {
+                action,
+                seconds: 0.0,
+            };
+        }
+
+        // The confirmed goalie comes posed from the server, the model is posed about its feet
+        let pose = action.pose(playing.seconds);
+        let standing = pose.standing(goalie.translation);
+        yoshi.translation = Vec3::new(
+            standing.x + pose.lunge,
+            GROUND_HEIGHT + pose.lift,
+            standing.z,
+        );
+        yoshi.rotation = pose.rotation() * Quat::from_rotation_y(YOSHI_FACING);
+    }
+
+    pub fn playing(settings: Res<ClientSettings>) -> bool {
+        settings.goalie
+    }
+
+    /// The camera looks down +z from behind the goalie, so the left of the screen is +x.
+    ///
+    /// - A / D or the arrow keys move, Q / E dive left / right, Space or W / Up jump
+    /// - holding a half of the screen moves that way, a second finger on the same half dives and
+    ///   one on the other half jumps
+    pub fn input(
+        mut client: Client,
+        keyboard_input: Res<Input<KeyCode>>,
+        touches: Res<Touches>,
+        window_query: Query<&Window, With<PrimaryWindow>>,
+        mut queued: Local<Option<GoalieActionValue>>,
+        mut tick_reader: EventReader<ClientTickEvent>,
+    ) {
+        let half_width = window_query
+            .get_single()
+            .map_or(0.0, |window| window.width() * 0.5);
+        // 1 towards +x, -1 towards -x
+        let side = |touch: &Touch| {
+            if touch.position().x < half_width {
+                1
+            } else {
+                -1
+            }
+        };
+        let dive = |side: i8| {
+            if side > 0 {
+                GoalieActionValue::DiveRight
+            } else {
+                GoalieActionValue::DiveLeft
+            }
+        };
+
+        let mut direction = keyboard_input.any_pressed([KeyCode::A, KeyCode::Left]) as i8
+            - keyboard_input.any_pressed([KeyCode::D, KeyCode::Right]) as i8;
+        if keyboard_input.just_pressed(KeyCode::Q) {
+            *queued = Some(dive(1));
+        }
+        if keyboard_input.just_pressed(KeyCode::E) {
+            *queued = Some(dive(-1));
+        }
+        if keyboard_input.any_just_pressed([KeyCode::Space, KeyCode::W, KeyCode::Up]) {
+            *queued = Some(GoalieActionValue::Jump);
+        }
+
+        let held = touches
+            .iter()
+            .find(|touch| !touches.just_pressed(touch.id()))
+            .map(side);
+        if let Some(held) = held {
+            direction = held;
+            for touch in touches.iter_just_pressed() {
+                *queued = Some(if side(touch) == held {
+                    dive(held)
+                } else {
+                    GoalieActionValue::Jump
+                });
+            }
+        } else if let Some(touch) = touches.iter().next() {
+            direction = side(touch);
+        }
+
+        for ClientTickEvent(client_tick) in tick_reader.iter() {
+            let command = GoalieCommand {
+                direction,
+                action: queued.take(),
+            };
+            client.send_tick_buffer_message::<PlayerCommandChannel, GoalieCommand>(
+                client_tick,
+                &command,
+            );
+        }
+    }
+}
+
 pub mod sync {
-    use super::components::{Confirmed, InterpPos, InterpRot};
+    use super::components::Confirmed;
+    use super::snapshot::{SnapshotBuffer, SnapshotStats};
     use protocol::components::{RepPhysics, UpdateWith};
 
     use bevy::prelude::*;
@@ -1477,57 +2117,29 @@ pub mod sync {
 
     pub fn serverside_entities(
         client: Client,
+        mut stats: ResMut<SnapshotStats>,
         mut query: Query<
-            // (&RepPhysics, &mut InterpPos, &mut Transform),
-            (
-                &RepPhysics,
-                &mut InterpPos,
-                &mut InterpRot,
-                &mut Transform,
-                &EntityKind,
-            ),
+            (&mut SnapshotBuffer, &mut Transform, &EntityKind),
             (With<Confirmed>, Without<super::events::Goalie>),
         >,
         mut query_yoshi: Query<&mut Transform, With<super::events::Goalie>>,
     ) {
-        for (rep_physics, mut interp, mut interp_rot, mut transform, kind) in query.iter_mut() {
-            // for (rep_physics, mut interp, mut transform) in query.iter_mut() {
-            if *rep_physics.translation_x != interp.next_x
-                || *rep_physics.translation_y != interp.next_y
-                || *rep_physics.translation_z != interp.next_z
-            {
-                interp.next(
-                    *rep_physics.translation_x,
-                    *rep_physics.translation_y,
-                    *rep_physics.translation_z,
-                );
-            }
-
-            let interp_amount = client.server_interpolation().unwrap();
-            interp.interpolate(interp_amount);
-            transform.translation.x = interp.interp_x;
-            transform.translation.y = interp.interp_y;
-            transform.translation.z = interp.interp_z;
-
-            if *rep_physics.rotation_x != interp_rot.next_x
-                || *rep_physics.rotation_y != interp_rot.next_y
-                || *rep_physics.rotation_z != interp_rot.next_z
-                || *rep_physics.rotation_w != interp_rot.next_w
-            {
-                interp_rot.next(
-                    *rep_physics.rotation_x,
-                    *rep_physics.rotation_y,
-                    *rep_physics.rotation_z,
-                    *rep_physics.rotation_w,
-                );
+        let (Some(now), Some(interpolation)) =
+            (client.server_tick(), client.server_interpolation())
+        else {
+            return;
+        };
+        // snapshot ages are counted from the start of the `now` tick
+        let age = stats.delay - interpolation;
+
+        for (mut buffer, mut transform, kind) in query.iter_mut() {
+            buffer.prune(now, age);
+            let sample = buffer.sample(now, age);
+            if sample.extrapolated {
+                stats.extrapolated += 1;
             }
-
-            interp_rot.interpolate(interp_amount);
-            transform.rotation.x = interp_rot.interp_x;
-            transform.rotation.y = interp_rot.interp_y;
-            transform.rotation.z = interp_rot.interp_z;
-            // transform.rotation.w = interp_rot.interp_w;
-            transform.rotation.w = *rep_physics.rotation_w;
+            transform.translation = sample.translation;
+            transform.rotation = sample.rotation;
 
             if let EntityKindValue::Goalie = *kind.value {
                 if let Ok(mut yoshi) = query_yoshi.get_single_mut() {
@@ -1587,8 +2199,9 @@ pub mod sync {
 //     // walking: Handle<AudioSource>,
 // }
 
-pub fn run() {
+pub fn run(settings: ClientSettings) {
     App::default()
+        .insert_resource(settings)
         // .add_state::<AppState>()
         // .add_loading_state(
         //     LoadingState::new(AppState::Loading).continue_to_state(AppState::Connect),
@@ -1618,15 +2231,17 @@ pub fn run() {
         ))
         // Background Color
         // .insert_resource(ClearColor(Color::hex("#87CEEB").unwrap()))
+        .init_resource::<snapshot::SnapshotStats>()
         .add_startup_system(init)
+        .add_system(reconnect::retry.run_if(resource_exists::<reconnect::Reconnect>()))
         .add_systems(
             (
                 events::connect_events,
                 events::disconnect_events,
-                events::reject_events,
                 // events::spawn_entity_events,
                 // events::despawn_entity_events,
                 events::insert_component_events,
+                snapshot::receive,
                 events::update_component_events,
                 events::remove_component_events,
                 events::message_events,
@@ -1635,18 +2250,38 @@ pub fn run() {
                 .in_set(ReceiveEvents),
         )
         .add_system(events::tick_events.in_set(Tick))
+        .add_system(goalie::input.in_set(Tick).run_if(goalie::playing))
+        .add_plugin(RulesPlugin)
+        .configure_set(RulesSet.after(Tick))
+        .insert_resource(FixedTime::new_from_secs(TIME_STEP))
+        .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+            schedule.add_system(magnus_effect.after(PhysicsSet::Writeback));
+        })
         .add_systems(
             (
                 // input::camera,
                 input::ball,
                 // button_handler,
                 // name_input,
+                snapshot::update_delay,
                 sync::serverside_entities,
+                snapshot::log_stats,
                 // debug_overlay,
             )
                 .chain()
                 .in_set(MainLoop),
         )
+        .add_system(
+            goalie::animate
+                .after(sync::serverside_entities)
+                .in_set(MainLoop),
+        )
+        .add_system(
+            spectator::camera
+                .after(sync::serverside_entities)
+                .in_set(MainLoop)
+                .run_if(spectator::spectating),
+        )
         // .configure_set(ReceiveEvents.run_if(in_state(AppState::InGame)))
         .configure_set(Tick.after(ReceiveEvents))
         .configure_set(MainLoop.after(Tick))
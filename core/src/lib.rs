@@ -1,6 +1,7 @@
 pub mod systems {
     use crate::components::*;
     use crate::constants::*;
+    use crate::goalie_ai::{BallView, GoalieAction};
 
     use bevy::prelude::*;
     use bevy_rapier3d::prelude::*;
@@ -26,66 +27,920 @@ pub mod systems {
         }
     }
 
+    /// Moves every goalie where its `GoalieBehavior::brain`, or the user in `GoalieControl`,
+    /// wants it within the patrol bounds and plays its dives and jumps.  Goalies only see balls in
+    /// their own arena.
     pub fn goalie(
         time: Res<Time>,
-        mut goalie_query: Query<(&mut Transform, &mut GoalieBehavior), Without<ExternalImpulse>>,
-        ball_query: Query<(&Transform, &Ball), With<ExternalImpulse>>,
+        mut goalie_query: Query<
+            (
+                &mut Transform,
+                &mut GoalieBehavior,
+                Option<&mut GoalieControl>,
+                Option<&Arena>,
+            ),
+            Without<ExternalImpulse>,
+        >,
+        ball_query: Query<
+            (&Transform, Option<&Velocity>, &Ball, Option<&Arena>),
+            With<ExternalImpulse>,
+        >,
         mut rand: ResMut<GlobalRng>,
     ) {
-        const SPEED: &[f32] = &[1.5, 2.0, 3.0, 4.0];
-        const ACTION_TIMES: &[f32] = &[0.1, 0.01, 0.15, 0.05];
-        const DIRECTION: &[f32] = &[0.0, 1.0, -1.0];
+        for (mut goalie_transform, mut goalie, mut control, goalie_arena) in goalie_query.iter_mut()
+        {
+            let balls: Vec<BallView> = ball_query
+                .iter()
+                .filter(|(.., ball_arena)| ball_arena.copied() == goalie_arena.copied())
+                .map(|(transform, velocity, ball, _)| BallView {
+                    translation: transform.translation,
+                    linvel: velocity.map_or(Vec3::ZERO, |velocity| velocity.linvel),
+                    angvel: velocity.map_or(Vec3::ZERO, |velocity| velocity.angvel),
+                    shot: ball.shot,
+                })
+                .collect();
+            let dt = time.delta_seconds();
+            let goalie = &mut *goalie;
+            let standing = goalie
+                .action
+                .pose(goalie.action_seconds)
+                .standing(goalie_transform.translation);
+            let mut standing = Transform::from_translation(standing);
+            goalie.action_seconds += dt;
 
-        let (mut goalie_transform, mut goalie) = goalie_query.get_single_mut().unwrap();
+            if goalie.action == GoalieAction::Stand {
+                let recovered = goalie.action_seconds >= GOALIE_RECOVER_TIME;
+                let x = match control.as_deref() {
+                    Some(control) => {
+                        let direction = control.direction.clamp(-1.0, 1.0);
+                        standing.translation.x + direction * GOALIE_CONTROL_SPEED * dt
+                    }
+                    None => goalie.brain.think(&standing, &balls, dt, &mut rand),
+                };
+                standing.translation.x = x.clamp(GOALIE_PATROL_MIN_X, GOALIE_PATROL_MAX_X);
 
-        // filter by balls that are 2.0 units or less away from the goalie
-        // Then sort them by the MIN z axis to find the closest ball
-        // to the goalie.  We then move the goalie towards the ball (if it exists).  Otherwise
-        // move normally if nothing was found.
-        let new_goalie_pos = ball_query
+                let action = match control.as_deref_mut() {
+                    // kept until the goalie is back on its feet
+                    Some(control) if recovered => control.action.take(),
+                    Some(_) => None,
+                    None if recovered => goalie.brain.act(&standing, &balls),
+                    None => None,
+                };
+                if let Some(action) = action {
+                    goalie.action = action;
+                    goalie.action_seconds = 0.0;
+                }
+            } else {
+                // no queueing another dive mid-air
+                if let Some(control) = control.as_deref_mut() {
+                    control.action = None;
+                }
+                if goalie.action_seconds >= goalie.action.duration() {
+                    goalie.action = GoalieAction::Stand;
+                    goalie.action_seconds = 0.0;
+                }
+            }
+
+            *goalie_transform = goalie
+                .action
+                .pose(goalie.action_seconds)
+                .apply(standing.translation);
+        }
+    }
+}
+
+/// Goalie behaviors.  A `GoalieBrain` decides where the goalie moves every physics step,
+/// `Difficulty` picks one for a room or a single player game.
+pub mod goalie_ai {
+    use crate::constants::*;
+
+    use std::{f32::consts::PI, str::FromStr};
+
+    use bevy::prelude::*;
+    use bevy_turborand::prelude::*;
+
+    /// What a goalie knows about a ball in its arena
+    #[derive(Clone, Debug)]
+    pub struct BallView {
+        pub translation: Vec3,
+        pub linvel: Vec3,
+        pub angvel: Vec3,
+        pub shot: bool,
+    }
+
+    pub trait GoalieBrain: Send + Sync + 'static {
+        /// The goalie's x for this step, clamped to the patrol bounds afterwards
+        fn think(
+            &mut self,
+            goalie: &Transform,
+            balls: &[BallView],
+            dt: f32,
+            rng: &mut GlobalRng,
+        ) -> f32;
+
+        /// Dive or jump now.  Only asked while the goalie is standing, after `think`.
+        fn act(&mut self, _goalie: &Transform, _balls: &[BallView]) -> Option<GoalieAction> {
+            None
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum GoalieAction {
+        #[default]
+        Stand,
+        /// Towards -x, the kicker's left
+        DiveLeft,
+        /// Towards +x, the kicker's right
+        DiveRight,
+        Jump,
+    }
+
+    impl GoalieAction {
+        /// Seconds from leaving the line until standing again
+        pub fn duration(self) -> f32 {
+            match self {
+                GoalieAction::Stand => 0.0,
+                GoalieAction::DiveLeft | GoalieAction::DiveRight => GOALIE_DIVE_TIME,
+                GoalieAction::Jump => GOALIE_JUMP_TIME,
+            }
+        }
+
+        /// The pose `seconds` into the action.  Goes out and comes back, fastest at the start.
+        pub fn pose(self, seconds: f32) -> GoaliePose {
+            let duration = self.duration();
+            let amount = if duration > 0.0 {
+                (PI * (seconds / duration).clamp(0.0, 1.0)).sin()
+            } else {
+                0.0
+            };
+            match self {
+                GoalieAction::Stand => GoaliePose::default(),
+                GoalieAction::DiveLeft => GoaliePose {
+                    lunge: -GOALIE_DIVE_LUNGE * amount,
+                    roll: GOALIE_DIVE_ROLL * amount,
+                    ..default()
+                },
+                GoalieAction::DiveRight => GoaliePose {
+                    lunge: GOALIE_DIVE_LUNGE * amount,
+                    roll: -GOALIE_DIVE_ROLL * amount,
+                    ..default()
+                },
+                GoalieAction::Jump => GoaliePose {
+                    lift: GOALIE_JUMP_HEIGHT * amount,
+                    ..default()
+                },
+            }
+        }
+    }
+
+    /// How far the goalie's feet moved and its body rolled compared to standing.  Used for the
+    /// collider on the server and for the model on the client.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct GoaliePose {
+        /// Up, in meters
+        pub lift: f32,
+        /// Along x, in meters
+        pub lunge: f32,
+        /// About z, in radians
+        pub roll: f32,
+    }
+
+    impl GoaliePose {
+        pub fn rotation(&self) -> Quat {
+            Quat::from_rotation_z(self.roll)
+        }
+
+        /// The transform of a goalie that would stand at `standing`.  The capsule rolls about its
+        /// feet.
+        pub fn apply(&self, standing: Vec3) -> Transform {
+            let height = Vec3::Y * (GOALIE_START.y - GROUND_HEIGHT);
+            let rotation = self.rotation();
+            let feet = standing - height + Vec3::new(self.lunge, self.lift, 0.0);
+            Transform::from_translation(feet + rotation * height).with_rotation(rotation)
+        }
+
+        /// Where a goalie in this pose at `posed` would stand
+        pub fn standing(&self, posed: Vec3) -> Vec3 {
+            posed - self.apply(Vec3::ZERO).translation
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum Difficulty {
+        /// `Patrol`
+        #[default]
+        Easy,
+        /// `HumanLike`
+        Normal,
+        /// `Predictive`
+        Hard,
+    }
+
+    impl Difficulty {
+        pub fn brain(self) -> Box<dyn GoalieBrain> {
+            match self {
+                Difficulty::Easy => Box::<Patrol>::default(),
+                Difficulty::Normal => Box::<HumanLike>::default(),
+                Difficulty::Hard => Box::<Predictive>::default(),
+            }
+        }
+    }
+
+    impl FromStr for Difficulty {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "easy" => Ok(Difficulty::Easy),
+                "normal" => Ok(Difficulty::Normal),
+                "hard" => Ok(Difficulty::Hard),
+                _ => Err(format!("{s}: expected easy, normal or hard")),
+            }
+        }
+    }
+
+    /// The original goalie.  Wanders along the line at random speeds and steps towards balls
+    /// less than 6 units in front of it.
+    #[derive(Default)]
+    pub struct Patrol {
+        seconds_left: f32,
+        direction: f32,
+        speed: f32,
+    }
+
+    impl GoalieBrain for Patrol {
+        fn think(
+            &mut self,
+            goalie: &Transform,
+            balls: &[BallView],
+            dt: f32,
+            rng: &mut GlobalRng,
+        ) -> f32 {
+            const SPEED: &[f32] = &[1.5, 2.0, 3.0, 4.0];
+            const ACTION_TIMES: &[f32] = &[0.1, 0.01, 0.15, 0.05];
+            const DIRECTION: &[f32] = &[0.0, 1.0, -1.0];
+
+            let x = goalie.translation.x;
+            let new_x = balls
+                .iter()
+                .filter(|ball| {
+                    let dist = ball.translation.z - goalie.translation.z;
+                    dist < 6.0 && dist > 0.0
+                })
+                .min_by(|a, b| a.translation.z.total_cmp(&b.translation.z))
+                .map(|ball| x + (ball.translation.x - x) * self.speed * 3.0 * TIME_STEP)
+                .unwrap_or_else(|| x + self.direction * self.speed * TIME_STEP)
+                .clamp(GOALIE_PATROL_MIN_X, GOALIE_PATROL_MAX_X);
+
+            // Reroll period
+            self.seconds_left -= dt;
+            if self.seconds_left <= 0.0 {
+                let rng = rng.get_mut();
+                self.seconds_left = *rng.sample(ACTION_TIMES).unwrap();
+                self.speed = *rng.sample(SPEED).unwrap();
+
+                match new_x {
+                    x if x == GOALIE_PATROL_MIN_X => self.direction = 1.0,
+                    x if x == GOALIE_PATROL_MAX_X => self.direction = -1.0,
+                    _ => {
+                        self.direction = *rng.sample(DIRECTION).unwrap();
+                    }
+                }
+            }
+            new_x
+        }
+    }
+
+    /// Reads every shot perfectly, curl included, and runs to where it crosses the line
+    pub struct Predictive {
+        /// m/s
+        pub max_speed: f32,
+    }
+
+    impl Default for Predictive {
+        fn default() -> Self {
+            Self { max_speed: 6.0 }
+        }
+    }
+
+    impl GoalieBrain for Predictive {
+        fn think(
+            &mut self,
+            goalie: &Transform,
+            balls: &[BallView],
+            dt: f32,
+            _rng: &mut GlobalRng,
+        ) -> f32 {
+            let target = incoming(goalie, balls).map_or(0.0, |(crossing, _)| crossing.x);
+            step_towards(goalie.translation.x, target, self.max_speed * dt)
+        }
+
+        fn act(&mut self, goalie: &Transform, balls: &[BallView]) -> Option<GoalieAction> {
+            let (crossing, seconds) = incoming(goalie, balls)?;
+            save(goalie, crossing, seconds)
+        }
+    }
+
+    /// Needs a moment to notice a shot, misjudges where it goes a little and is slower than
+    /// `Predictive`
+    pub struct HumanLike {
+        /// Seconds between a shot coming in and the goalie moving
+        pub reaction_time: f32,
+        /// m/s
+        pub max_speed: f32,
+        /// Largest error in judging where the shot crosses the line
+        pub misjudge: f32,
+        /// Seconds since the current shot was noticed and how far off the goalie's read is
+        noticed: Option<(f32, f32)>,
+    }
+
+    impl Default for HumanLike {
+        fn default() -> Self {
+            Self {
+                reaction_time: 0.25,
+                max_speed: 4.0,
+                misjudge: 0.4,
+                noticed: None,
+            }
+        }
+    }
+
+    impl GoalieBrain for HumanLike {
+        fn think(
+            &mut self,
+            goalie: &Transform,
+            balls: &[BallView],
+            dt: f32,
+            rng: &mut GlobalRng,
+        ) -> f32 {
+            let x = goalie.translation.x;
+            let Some((crossing, _)) = incoming(goalie, balls) else {
+                self.noticed = None;
+                // wander back to the middle
+                return step_towards(x, 0.0, self.max_speed * 0.5 * dt);
+            };
+
+            let misjudge = self.misjudge;
+            let (seconds, error) = self
+                .noticed
+                .get_or_insert_with(|| (0.0, (rng.get_mut().f32() * 2.0 - 1.0) * misjudge));
+            *seconds += dt;
+            if *seconds < self.reaction_time {
+                return x;
+            }
+            step_towards(x, crossing.x + *error, self.max_speed * dt)
+        }
+
+        fn act(&mut self, goalie: &Transform, balls: &[BallView]) -> Option<GoalieAction> {
+            let (seconds, error) = self.noticed?;
+            if seconds < self.reaction_time {
+                return None;
+            }
+            let (crossing, seconds) = incoming(goalie, balls)?;
+            save(goalie, crossing + Vec3::X * error, seconds)
+        }
+    }
+
+    fn step_towards(x: f32, target: f32, max_step: f32) -> f32 {
+        x + (target - x).clamp(-max_step, max_step)
+    }
+
+    /// Where the shot closest to the goalie crosses its line, and roughly in how many seconds
+    fn incoming(goalie: &Transform, balls: &[BallView]) -> Option<(Vec3, f32)> {
+        balls
             .iter()
-            .filter(|(q, _)| {
-                let dist = q.translation.z - goalie_transform.translation.z;
-                dist < 6.0 && dist > 0.0
+            .filter(|ball| ball.shot)
+            .filter_map(|ball| {
+                let crossing = predict_crossing(ball, goalie.translation.z)?;
+                let seconds = (goalie.translation.z - ball.translation.z) / ball.linvel.z;
+                Some((ball.translation.z, crossing, seconds))
             })
-            .min_by(|a, b| a.0.translation.z.partial_cmp(&b.0.translation.z).unwrap())
-            .map(|q| {
-                let to_x = q.0.translation.x - goalie_transform.translation.x;
-                let speed = goalie.speed * 3.0;
-                goalie_transform.translation.x + to_x * speed * TIME_STEP
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, crossing, seconds)| (crossing, seconds))
+    }
+
+    /// A dive or jump for a shot crossing the line at `crossing` in `seconds`, when standing won't
+    /// stop it and the ball is close enough to commit
+    fn save(goalie: &Transform, crossing: Vec3, seconds: f32) -> Option<GoalieAction> {
+        if seconds > GOALIE_SAVE_WINDOW {
+            return None;
+        }
+        let reach = GOALIE_RADIUS + BALL_RADIUS;
+        let dx = crossing.x - goalie.translation.x;
+        if crossing.y > goalie.translation.y + GOALIE_HEIGHT * 0.5 + reach {
+            (dx.abs() < reach).then_some(GoalieAction::Jump)
+        } else if dx.abs() > reach {
+            Some(if dx < 0.0 {
+                GoalieAction::DiveLeft
+            } else {
+                GoalieAction::DiveRight
             })
-            .unwrap_or_else(|| {
-                goalie_transform.translation.x + goalie.direction * goalie.speed * TIME_STEP
-            });
-        goalie_transform.translation.x =
-            new_goalie_pos.clamp(GOALIE_PATROL_MIN_X, GOALIE_PATROL_MAX_X);
-
-        // Reroll period
-        goalie.seconds_left -= time.delta_seconds();
-        if goalie.seconds_left <= 0.0 {
-            let rand = rand.get_mut();
-            goalie.seconds_left = *rand.sample(ACTION_TIMES).unwrap();
-            goalie.speed = *rand.sample(SPEED).unwrap();
-
-            match goalie_transform.translation.x {
-                x if x == GOALIE_PATROL_MIN_X => goalie.direction = 1.0,
-                x if x == GOALIE_PATROL_MAX_X => goalie.direction = -1.0,
-                _ => {
-                    goalie.direction = *rand.sample(DIRECTION).unwrap();
-                }
+        } else {
+            None
+        }
+    }
+
+    /// Mirrors the ball's `Damping`
+    const BALL_LINEAR_DAMPING: f32 = 1.0;
+    const BALL_ANGULAR_DAMPING: f32 = 2.0;
+    /// Seconds `predict_crossing` looks ahead
+    const PREDICTION_HORIZON: f32 = 3.0;
+
+    /// Steps the ball's flight the way rapier and `systems::magnus_effect` will, until it crosses
+    /// the plane at `z`.  `None` for balls that aren't heading there or won't make it in time.
+    pub fn predict_crossing(ball: &BallView, z: f32) -> Option<Vec3> {
+        if ball.linvel.z >= 0.0 || ball.translation.z <= z {
+            return None;
+        }
+
+        let gravity = Vec3::new(0.0, -9.81, 0.0);
+        let mut position = ball.translation;
+        let mut linvel = ball.linvel;
+        let mut angvel = ball.angvel;
+        // `magnus_effect` keeps adding to the force while the ball is in the air
+        let mut force = Vec3::ZERO;
+        for _ in 0..(PREDICTION_HORIZON / TIME_STEP) as usize {
+            if position.y > 0.21 {
+                force += MAGNUS_CONSTANT * TIME_STEP * angvel.cross(linvel);
+            } else {
+                force = Vec3::ZERO;
+            }
+            linvel += (gravity + force / BALL_MASS) * TIME_STEP;
+            linvel /= 1.0 + TIME_STEP * BALL_LINEAR_DAMPING;
+            angvel /= 1.0 + TIME_STEP * BALL_ANGULAR_DAMPING;
+
+            let mut next = position + linvel * TIME_STEP;
+            if next.y < BALL_RADIUS {
+                next.y = BALL_RADIUS;
+                linvel.y = -linvel.y * 0.5;
+            }
+            if next.z <= z {
+                let t = (position.z - z) / (position.z - next.z);
+                return Some(position.lerp(next, t));
+            }
+            position = next;
+        }
+        None
+    }
+}
+
+/// Several matches can share one physics world.  Everything belonging to a match carries its
+/// `Arena` and `ArenaHooks` stops balls from touching another arena's goal and goalie.  Colliders
+/// without an `Arena` (the ground) touch everything.
+pub mod arena {
+    use crate::components::Arena;
+
+    use bevy::ecs::system::SystemParam;
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+
+    /// Use as `RapierPhysicsPlugin::<ArenaHooks>`.  Only pairs where one of the colliders has
+    /// `ActiveHooks::FILTER_CONTACT_PAIRS | ActiveHooks::FILTER_INTERSECTION_PAIR` are checked.
+    #[derive(SystemParam)]
+    pub struct ArenaHooks<'w, 's> {
+        arenas: Query<'w, 's, &'static Arena>,
+    }
+
+    impl ArenaHooks<'_, '_> {
+        fn same_arena(&self, context: &PairFilterContextView) -> bool {
+            match (
+                self.arenas.get(context.collider1()),
+                self.arenas.get(context.collider2()),
+            ) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => true,
+            }
+        }
+    }
+
+    impl BevyPhysicsHooks for ArenaHooks<'_, '_> {
+        fn filter_contact_pair(&self, context: PairFilterContextView) -> Option<SolverFlags> {
+            self.same_arena(&context)
+                .then_some(SolverFlags::COMPUTE_IMPULSES)
+        }
+
+        fn filter_intersection_pair(&self, context: PairFilterContextView) -> bool {
+            self.same_arena(&context)
+        }
+    }
+
+    pub fn active_hooks() -> ActiveHooks {
+        ActiveHooks::FILTER_CONTACT_PAIRS | ActiveHooks::FILTER_INTERSECTION_PAIR
+    }
+}
+
+/// Colliders of the playing field, shared by the server and the simulation tests so both play on
+/// the same pitch.
+pub mod field {
+    use crate::arena::active_hooks;
+    use crate::components::{Arena, Ball, PointZone};
+    use crate::constants::*;
+
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+
+    /// Group2 is the ball group.  Group1 is the goal frame / point zone and Group3 is the goalie.
+    pub fn ball_collision_groups() -> CollisionGroups {
+        CollisionGroups::new(Group::GROUP_2, Group::GROUP_1 | Group::GROUP_3)
+    }
+
+    pub fn goalie_collision_groups() -> CollisionGroups {
+        CollisionGroups::new(Group::GROUP_3, Group::GROUP_2)
+    }
+
+    /// The ground is shared by every arena
+    pub fn spawn_ground(commands: &mut Commands) -> Entity {
+        commands
+            .spawn((
+                Name::new("Ground"),
+                TransformBundle::from(Transform::from_xyz(0.0, GROUND_HEIGHT, 0.0)),
+                Collider::cuboid(GROUND_SIZE, 0.0, GROUND_SIZE),
+                RigidBody::KinematicPositionBased,
+                Friction::new(100.0),
+            ))
+            .id()
+    }
+
+    /// A goal rigid-body with the frame and point zone colliders attached as children
+    pub fn spawn_goal(commands: &mut Commands, arena: Arena) -> Entity {
+        let rad = 0.2;
+        commands
+            .spawn((
+                Name::new("Goal"),
+                arena,
+                TransformBundle::from(Transform::from_xyz(0.0, GROUND_HEIGHT, 32.0)),
+                RigidBody::KinematicPositionBased,
+                CollisionGroups::new(Group::GROUP_1, Group::GROUP_2),
+            ))
+            .with_children(|p| {
+                p.spawn((
+                    Name::new("FrameTop"),
+                    arena,
+                    TransformBundle::from(Transform::from_xyz(0.0, rad * 10.0, 0.0)),
+                    Collider::cuboid(rad * 10.0, rad * 0.5, rad),
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+                p.spawn((
+                    Name::new("FrameLeft"),
+                    arena,
+                    TransformBundle::from(Transform::from_xyz(rad * 10.0, rad * 5.0, 0.0)),
+                    Collider::cuboid(rad * 0.5, rad * 5.0, rad),
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+                p.spawn((
+                    Name::new("FrameRight"),
+                    arena,
+                    TransformBundle::from(Transform::from_xyz(-rad * 10.0, rad * 5.0, 0.0)),
+                    Collider::cuboid(rad * 0.5, rad * 5.0, rad),
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+                p.spawn((
+                    Name::new("PointZone"),
+                    arena,
+                    PointZone,
+                    TransformBundle::from(Transform::from_xyz(0.0, rad * 5.0, (-rad * 0.5) - rad)),
+                    Sensor,
+                    Collider::cuboid(rad * 10.0, rad * 5.0, rad * 0.5),
+                    ActiveEvents::COLLISION_EVENTS,
+                ));
+            })
+            .id()
+    }
+
+    /// Physics of a goalie standing at `translation`, add a `GoalieBehavior` to make it move
+    pub fn goalie_body(arena: Arena, translation: Vec3) -> impl Bundle {
+        (
+            Name::new("Goalie"),
+            arena,
+            TransformBundle::from_transform(Transform::from_translation(translation)),
+            RigidBody::KinematicPositionBased,
+            goalie_collision_groups(),
+            Collider::capsule_y(GOALIE_HEIGHT * 0.5, GOALIE_RADIUS),
+            GravityScale::default(),
+            Damping {
+                linear_damping: 1.0,
+                angular_damping: 5.0,
+            },
+            Restitution {
+                coefficient: 1.0,
+                combine_rule: CoefficientCombineRule::Average,
+            },
+            Velocity::zero(),
+            Sleeping::default(),
+            ActiveEvents::COLLISION_EVENTS,
+            active_hooks(),
+        )
+    }
+
+    /// A ball on `BALL_START`, ready to be kicked once it has dropped
+    pub fn ball_body(arena: Arena) -> impl Bundle {
+        (
+            (Name::new("Ball"), arena, Ball::default()),
+            TransformBundle::from_transform(Transform::from_translation(BALL_START)),
+            RigidBody::Dynamic,
+            ball_collision_groups(),
+            Collider::ball(BALL_RADIUS),
+            ColliderMassProperties::Mass(BALL_MASS),
+            Velocity::zero(),
+            Friction::new(5.0),
+            ExternalForce::default(),
+            ExternalImpulse::default(),
+            GravityScale::default(),
+            Damping {
+                linear_damping: 1.0,
+                angular_damping: 2.0,
+            },
+            Restitution {
+                coefficient: 1.0,
+                combine_rule: CoefficientCombineRule::Average,
+            },
+            Sleeping::default(),
+            active_hooks(),
+        )
+    }
+}
+
+/// Game rules shared by single player, the server and client-side prediction so they all behave
+/// the same way.
+pub mod rules {
+    use crate::components::*;
+    use crate::constants::*;
+
+    use bevy::prelude::*;
+    use bevy_rapier3d::prelude::*;
+
+    #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+    pub struct RulesSet;
+
+    /// Adds the kick model, reset policy and shot outcome detection.  Systems are added to
+    /// `RulesSet` so each app can decide when the rules run.
+    pub struct RulesPlugin;
+
+    impl Plugin for RulesPlugin {
+        fn build(&self, app: &mut App) {
+            app.init_resource::<KickModel>()
+                .init_resource::<ResetPolicy>()
+                .add_event::<ShotOutcome>()
+                .add_systems((detect_shot_outcomes, ball_reset).chain().in_set(RulesSet));
+        }
+    }
+
+    /// How a ray hit on the ball turns into an impulse
+    #[derive(Resource, Clone, Debug)]
+    pub struct KickModel {
+        /// Added to the y component of the hit normal, so clicking the middle of the ball lifts it
+        pub normal_y_bias: f32,
+        pub force: Vec3,
+        /// Scales the spin picked up from kicking off-center
+        pub torque_scale: f32,
+        /// The ball has to be below this height (settled on the floor) to be kicked
+        pub max_kick_height: f32,
+    }
+
+    impl Default for KickModel {
+        fn default() -> Self {
+            Self {
+                normal_y_bias: -0.8,
+                force: Vec3::new(-2.0, -3.0, -13.0),
+                torque_scale: 0.15,
+                max_kick_height: 0.01,
+            }
+        }
+    }
+
+    impl KickModel {
+        pub fn impulse(&self, ray_normal: Vec3, ray_point: Vec3, center: Vec3) -> ExternalImpulse {
+            let ray_normal = Vec3::new(
+                ray_normal.x,
+                ray_normal.y + self.normal_y_bias,
+                ray_normal.z,
+            );
+            let impulse = ray_normal * self.force;
+            let mut ext_i = ExternalImpulse::at_point(impulse, ray_point, center);
+            ext_i.torque_impulse *= self.torque_scale;
+            ext_i
+        }
+
+        //#HACK we stop shots from happening unless the ball is settled on the floor
+        //this works for now minus the spamming inbetween the bounce of the ball when spawned.
+        //probably should set this to a cooldown.
+        pub fn can_kick(&self, ball: &Ball, transform: &Transform) -> bool {
+            !ball.shot && transform.translation.y < self.max_kick_height
+        }
+
+        /// Kicks the ball if it can be kicked.  Returns true if the ball was kicked.
+        pub fn kick(
+            &self,
+            ray_normal: Vec3,
+            ray_point: Vec3,
+            transform: &Transform,
+            ball: &mut Ball,
+            ext_i: &mut ExternalImpulse,
+        ) -> bool {
+            if !self.can_kick(ball, transform) {
+                return false;
+            }
+
+            *ext_i = self.impulse(ray_normal, ray_point, transform.translation);
+            ball.shot = true;
+            true
+        }
+    }
+
+    /// When a shot ball goes back to `BALL_START`
+    #[derive(Resource, Clone, Debug)]
+    pub struct ResetPolicy {
+        /// Seconds after the kick before a missed ball is reset
+        pub miss_wait: f32,
+        /// Seconds after the kick before a scored ball is reset
+        pub score_wait: f32,
+    }
+
+    impl Default for ResetPolicy {
+        fn default() -> Self {
+            Self {
+                miss_wait: BALL_SHOT_WAIT_TIME,
+                score_wait: 1.0,
+            }
+        }
+    }
+
+    impl ResetPolicy {
+        pub fn should_reset(&self, ball: &Ball) -> bool {
+            ball.force_reset
+                || ball.shot_elapsed >= self.miss_wait
+                || (ball.scored && ball.shot_elapsed >= self.score_wait)
+        }
+    }
+
+    pub fn reset_ball(
+        transform: &mut Transform,
+        ball: &mut Ball,
+        ext_f: &mut ExternalForce,
+        ext_i: &mut ExternalImpulse,
+        velocity: &mut Velocity,
+    ) {
+        *ext_f = ExternalForce::default();
+        *ext_i = ExternalImpulse::default();
+        *velocity = Velocity::zero();
+        *transform = Transform::from_translation(BALL_START);
+        *ball = Ball::default();
+    }
+
+    pub fn ball_reset(
+        time: Res<Time>,
+        policy: Res<ResetPolicy>,
+        mut ball_query: Query<(
+            Entity,
+            &mut Transform,
+            &mut Ball,
+            &mut ExternalForce,
+            &mut ExternalImpulse,
+            &mut Velocity,
+        )>,
+        mut outcomes: EventWriter<ShotOutcome>,
+    ) {
+        for (entity, mut transform, mut ball, mut ext_f, mut ext_i, mut velocity) in
+            ball_query.iter_mut()
+        {
+            if ball.shot {
+                ball.shot_elapsed += time.delta_seconds();
+            }
+
+            if policy.should_reset(&ball) {
+                resolve_denied(entity, &mut ball, &mut outcomes);
+                reset_ball(
+                    &mut transform,
+                    &mut ball,
+                    &mut ext_f,
+                    &mut ext_i,
+                    &mut velocity,
+                );
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum ShotOutcomeKind {
+        Goal,
+        DeniedGoalie,
+        DeniedFrame,
+    }
+
+    pub struct ShotOutcome {
+        pub ball: Entity,
+        pub kind: ShotOutcomeKind,
+    }
+
+    /// Turns rapier collisions between balls and the goal into one `ShotOutcome` per shot.  Balls
+    /// don't hit balls and the goalie doesn't hit the frame, so anything else a ball touches with
+    /// collision events enabled is the goal frame.  A goal counts as soon as the ball touches the
+    /// point zone.  A goalie or frame hit only counts once the ball heads away from the goal, or
+    /// when it is reset, so a shot off the post that still goes in is a goal.
+    pub fn detect_shot_outcomes(
+        mut collision_events: EventReader<CollisionEvent>,
+        mut ball_query: Query<(Entity, &mut Ball, Option<&Velocity>)>,
+        point_query: Query<(), With<PointZone>>,
+        goalie_query: Query<(), With<GoalieBehavior>>,
+        mut outcomes: EventWriter<ShotOutcome>,
+    ) {
+        for event in collision_events.iter() {
+            let CollisionEvent::Started(entity1, entity2, _) = event else {
+                continue;
+            };
+
+            let (ball_entity, other) = if ball_query.contains(*entity2) {
+                (*entity2, *entity1)
+            } else if ball_query.contains(*entity1) {
+                (*entity1, *entity2)
+            } else {
+                continue;
+            };
+
+            let Ok((_, mut ball, _)) = ball_query.get_mut(ball_entity) else {
+                continue;
+            };
+            if !ball.shot || ball.resolved {
+                continue;
+            }
+
+            if point_query.contains(other) {
+                ball.scored = true;
+                ball.resolved = true;
+                outcomes.send(ShotOutcome {
+                    ball: ball_entity,
+                    kind: ShotOutcomeKind::Goal,
+                });
+            } else if ball.denied.is_none() {
+                ball.denied = Some(if goalie_query.contains(other) {
+                    ShotOutcomeKind::DeniedGoalie
+                } else {
+                    ShotOutcomeKind::DeniedFrame
+                });
+            }
+        }
+
+        for (entity, mut ball, velocity) in ball_query.iter_mut() {
+            let heading_out = velocity.map_or(true, |velocity| velocity.linvel.z >= 0.0);
+            if heading_out {
+                resolve_denied(entity, &mut ball, &mut outcomes);
             }
         }
     }
+
+    /// Sends the pending deny of a shot that can't score anymore
+    fn resolve_denied(entity: Entity, ball: &mut Ball, outcomes: &mut EventWriter<ShotOutcome>) {
+        if ball.resolved {
+            return;
+        }
+        if let Some(kind) = ball.denied {
+            ball.resolved = true;
+            outcomes.send(ShotOutcome { ball: entity, kind });
+        }
+    }
 }
 
 pub mod components {
+    use crate::goalie_ai::{Difficulty, GoalieAction, GoalieBrain};
+    use crate::rules::ShotOutcomeKind;
+
     use bevy::prelude::*;
 
-    #[derive(Component, Default)]
+    #[derive(Component)]
     pub struct GoalieBehavior {
-        pub seconds_left: f32,
+        pub brain: Box<dyn GoalieBrain>,
+        pub action: GoalieAction,
+        /// Since `action` started, or since the goalie got up again while standing
+        pub action_seconds: f32,
+    }
+
+    impl GoalieBehavior {
+        pub fn new(difficulty: Difficulty) -> Self {
+            Self::with_brain(difficulty.brain())
+        }
+
+        pub fn with_brain(brain: Box<dyn GoalieBrain>) -> Self {
+            Self {
+                brain,
+                action: GoalieAction::Stand,
+                action_seconds: 0.0,
+            }
+        }
+    }
+
+    impl Default for GoalieBehavior {
+        fn default() -> Self {
+            Self::new(Difficulty::default())
+        }
+    }
+
+    /// A user plays this goalie, its `GoalieBehavior::brain` is ignored while this is set
+    #[derive(Component, Default)]
+    pub struct GoalieControl {
+        /// -1.0 towards -x, 1.0 towards +x
         pub direction: f32,
-        pub speed: f32,
+        /// Played as soon as the goalie is standing
+        pub action: Option<GoalieAction>,
     }
 
     #[derive(Component, Default)]
@@ -94,7 +949,19 @@ pub mod components {
         pub scored: bool,
         pub force_reset: bool,
         pub shot_elapsed: f32,
+        /// The goalie or the frame stopped the shot, unless the ball still goes in
+        pub denied: Option<ShotOutcomeKind>,
+        /// The shot's `ShotOutcome` has been sent, there is only one per shot
+        pub resolved: bool,
     }
+
+    /// Sensor inside the goal mouth.  A ball touching it has scored.
+    #[derive(Component, Default)]
+    pub struct PointZone;
+
+    /// Which match a ball, goal or goalie belongs to, see `arena`
+    #[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Arena(pub u32);
 }
 
 pub mod constants {
@@ -118,6 +985,18 @@ pub mod constants {
     pub const GOALIE_START: Vec3 = Vec3::new(0.0, GOALIE_HEIGHT, 32.8);
     pub const GOALIE_PATROL_MAX_X: f32 = 1.5;
     pub const GOALIE_PATROL_MIN_X: f32 = -1.5;
+    pub const GOALIE_DIVE_TIME: f32 = 0.9;
+    pub const GOALIE_DIVE_LUNGE: f32 = 0.4;
+    /// Nearly flat
+    pub const GOALIE_DIVE_ROLL: f32 = 1.3;
+    pub const GOALIE_JUMP_TIME: f32 = 0.6;
+    pub const GOALIE_JUMP_HEIGHT: f32 = 0.5;
+    /// Seconds standing after a dive or jump before the next one
+    pub const GOALIE_RECOVER_TIME: f32 = 0.3;
+    /// m/s of a goalie played by a user
+    pub const GOALIE_CONTROL_SPEED: f32 = 4.0;
+    /// Brains commit to a dive or jump for shots crossing the line in less seconds than this
+    pub const GOALIE_SAVE_WINDOW: f32 = 0.3;
 
     pub const MAGNUS_AIR_DENSITY: f32 = 3.225; // kg/m^3
     pub const MAGNUS_CONSTANT: f32 = 4.0 / 3.0 * PI * MAGNUS_AIR_DENSITY * 0.001331; //f32::powf(BALL_RADIUS, 3.0);
@@ -128,6 +1007,9 @@ pub mod constants {
     pub const BIRDS_EYE_CAM_LOOK: Vec3 = Vec3::new(0.0, -500.0, 0.0);
     pub const KICK_CAM: Transform = Transform::from_xyz(0.0, 1.0, 43.8);
     pub const KICK_CAM_LOOK: Vec3 = Vec3::new(0.0, -7.0, 0.0);
+    /// Behind the goalie, for users playing it
+    pub const GOALIE_CAM: Transform = Transform::from_xyz(0.0, 1.8, 29.5);
+    pub const GOALIE_CAM_LOOK: Vec3 = Vec3::new(0.0, 0.5, 42.0);
 }
 
 pub mod debug {
@@ -0,0 +1,136 @@
+use powerbaby_core::{
+    constants::*,
+    goalie_ai::{
+        predict_crossing, BallView, Difficulty, GoalieAction, GoalieBrain, HumanLike, Predictive,
+    },
+};
+
+use bevy::prelude::{Transform, Vec3};
+use bevy_turborand::prelude::*;
+
+fn shot(linvel: Vec3, angvel: Vec3) -> BallView {
+    BallView {
+        translation: Vec3::new(0.5, 1.0, 40.0),
+        linvel,
+        angvel,
+        shot: true,
+    }
+}
+
+#[test]
+fn straight_shot_crosses_in_line() {
+    let ball = shot(Vec3::new(0.0, 0.0, -20.0), Vec3::ZERO);
+    let crossing = predict_crossing(&ball, GOALIE_START.z).expect("heading for the goal");
+    assert!((crossing.x - 0.5).abs() < 1e-4);
+    assert!((crossing.z - GOALIE_START.z).abs() < 1e-3);
+}
+
+#[test]
+fn spin_curls_the_shot() {
+    // angvel x linvel points towards -x
+    let ball = shot(Vec3::new(0.0, 0.0, -20.0), Vec3::new(0.0, 10.0, 0.0));
+    let crossing = predict_crossing(&ball, GOALIE_START.z).expect("heading for the goal");
+    assert!(crossing.x < 0.5, "crossed at {crossing}");
+}
+
+#[test]
+fn balls_going_away_never_cross() {
+    let ball = shot(Vec3::new(0.0, 2.0, 5.0), Vec3::ZERO);
+    assert_eq!(predict_crossing(&ball, GOALIE_START.z), None);
+}
+
+#[test]
+fn difficulty_from_str() {
+    assert_eq!("hard".parse(), Ok(Difficulty::Hard));
+    assert!("impossible".parse::<Difficulty>().is_err());
+}
+
+#[test]
+fn hard_goalie_dives_for_shots_out_of_reach() {
+    let goalie = Transform::from_translation(GOALIE_START);
+    let mut brain = Predictive::default();
+    let mut ball = shot(Vec3::new(0.0, 0.0, -20.0), Vec3::ZERO);
+    ball.translation = Vec3::new(1.4, 0.5, 35.0);
+    assert_eq!(
+        brain.act(&goalie, &[ball.clone()]),
+        Some(GoalieAction::DiveRight)
+    );
+
+    // too early to commit
+    ball.translation.z = 42.0;
+    assert_eq!(brain.act(&goalie, &[ball]), None);
+}
+
+#[test]
+fn normal_goalie_waits_out_its_reaction_time() {
+    let goalie = Transform::from_translation(GOALIE_START);
+    let mut rng = GlobalRng::with_seed(7);
+    let mut brain = HumanLike::default();
+    brain.misjudge = 0.0;
+    let ball = shot(Vec3::new(0.0, 0.0, -20.0), Vec3::ZERO);
+    let dt = brain.reaction_time / 2.0;
+
+    let x = brain.think(&goalie, &[ball.clone()], dt, &mut rng);
+    assert_eq!(x, GOALIE_START.x);
+    assert_eq!(brain.act(&goalie, &[ball.clone()]), None);
+
+    let x = brain.think(&goalie, &[ball], dt, &mut rng);
+    assert!(x > GOALIE_START.x, "moved to {x}");
+
+    // a new shot is noticed late again
+    brain.think(&goalie, &[], dt, &mut rng);
+    let ball = shot(Vec3::new(0.0, 0.0, -20.0), Vec3::ZERO);
+    let x = brain.think(&goalie, &[ball], dt, &mut rng);
+    assert_eq!(x, GOALIE_START.x);
+}
+
+#[test]
+fn normal_goalie_misjudges_within_bounds() {
+    let goalie = Transform::from_translation(GOALIE_START);
+    let ball = shot(Vec3::new(0.0, 0.0, -20.0), Vec3::ZERO);
+    let crossing = predict_crossing(&ball, GOALIE_START.z).expect("heading for the goal");
+    let mut rng = GlobalRng::with_seed(7);
+    let mut reads = Vec::new();
+    for _ in 0..20 {
+        let mut brain = HumanLike::default();
+        brain.reaction_time = 0.0;
+        // reaches its read in one step
+        brain.max_speed = 1000.0;
+        let x = brain.think(&goalie, &[ball.clone()], 0.1, &mut rng);
+        assert!((x - crossing.x).abs() <= brain.misjudge, "read {x}");
+        // the read sticks for the rest of the shot
+        let again = brain.think(&goalie, &[ball.clone()], 0.1, &mut rng);
+        assert_eq!(again, x);
+        reads.push(x);
+    }
+    assert!(reads.iter().any(|x| (x - reads[0]).abs() > 1e-3));
+}
+
+#[test]
+fn poses_round_trip() {
+    let standing = Vec3::new(0.7, GOALIE_START.y, GOALIE_START.z);
+    for action in [
+        GoalieAction::Stand,
+        GoalieAction::DiveLeft,
+        GoalieAction::DiveRight,
+        GoalieAction::Jump,
+    ] {
+        let pose = action.pose(action.duration() * 0.4);
+        let posed = pose.apply(standing);
+        assert!(pose.standing(posed.translation).distance(standing) < 1e-5);
+    }
+    let stand = GoalieAction::Stand.pose(1.0).apply(standing);
+    assert!(stand.translation.distance(standing) < 1e-5);
+}
+
+#[test]
+fn dives_go_sideways_and_down() {
+    let half = GOALIE_DIVE_TIME * 0.5;
+    let left = GoalieAction::DiveLeft.pose(half).apply(GOALIE_START);
+    let right = GoalieAction::DiveRight.pose(half).apply(GOALIE_START);
+    assert!(left.translation.x < GOALIE_START.x - GOALIE_DIVE_LUNGE);
+    assert!(right.translation.x > GOALIE_START.x + GOALIE_DIVE_LUNGE);
+    assert!(left.translation.y < GOALIE_START.y);
+    // head first
+    assert!((left.rotation * Vec3::Y).x < -0.9);
+}
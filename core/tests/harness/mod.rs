@@ -0,0 +1,268 @@
+//! Headless, deterministic simulation of the core physics.
+//!
+//! Builds a minimal Bevy `App` set up like the server: rapier stepping at `TIME_STEP`, the
+//! `goalie` and `magnus_effect` systems in `FixedUpdate`, the shared rules and the `field`
+//! colliders.  Kicks are injected one at a time and each one is run until the ball resets.
+use powerbaby_core::{
+    arena::ArenaHooks,
+    components::{Arena, Ball, GoalieBehavior, GoalieControl},
+    constants::*,
+    field,
+    goalie_ai::{BallView, Difficulty, GoalieBrain},
+    rules::{KickModel, RulesPlugin, ShotOutcome, ShotOutcomeKind},
+    systems::{goalie, magnus_effect},
+};
+
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::time::{TimePlugin, TimeUpdateStrategy};
+use bevy_rapier3d::prelude::*;
+use bevy_turborand::prelude::*;
+
+/// Upper bound on steps spent waiting for the ball to settle or a shot to finish
+const MAX_STEPS: usize = 60 * 10;
+
+#[derive(Clone, Debug)]
+pub struct Kick {
+    pub ray_normal: Vec3,
+    pub ray_point: Vec3,
+}
+
+impl Kick {
+    /// The spacebar "straight shot" from `controls`
+    pub fn straight() -> Self {
+        Self {
+            ray_normal: Vec3::new(0.015694855, -0.011672409, 0.9998087),
+            ray_point: Vec3::new(0.0017264052, 0.0070980787, 42.109978),
+        }
+    }
+
+    /// Hit dead center with no lift, the ball skids along the ground
+    pub fn low_drive() -> Self {
+        Self {
+            ray_normal: Vec3::new(0.0, 0.8, 1.0),
+            ray_point: Vec3::new(0.0, 0.01, BALL_START.z),
+        }
+    }
+
+    /// Low drive at the +x post, dead center so there's no spin to curl it
+    pub fn post() -> Self {
+        Self {
+            ray_normal: Vec3::new(-1.3, 0.8, 1.0),
+            ray_point: Vec3::new(0.0, 0.01, BALL_START.z),
+        }
+    }
+
+    /// Kicks the ball away from the goal
+    pub fn backwards() -> Self {
+        Self {
+            ray_normal: Vec3::new(0.0, 0.8, -1.0),
+            ray_point: Vec3::new(0.0, 0.01, BALL_START.z),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Goal,
+    GoalieDeny,
+    FrameDeny,
+    Miss,
+}
+
+pub enum GoalieMode {
+    /// The regular random patrol
+    Patrol,
+    /// Goalie never moves from this x position
+    Stationary(f32),
+    /// One of the real goalies
+    Difficulty(Difficulty),
+}
+
+struct Stationary;
+
+impl GoalieBrain for Stationary {
+    fn think(&mut self, goalie: &Transform, _: &[BallView], _: f32, _: &mut GlobalRng) -> f32 {
+        goalie.translation.x
+    }
+}
+
+pub struct Simulation {
+    app: App,
+    now: Instant,
+    ball: Entity,
+    goalie: Entity,
+}
+
+impl Simulation {
+    pub fn new(goalie_mode: GoalieMode) -> Self {
+        let mut app = App::new();
+        app.add_plugin(TaskPoolPlugin::default())
+            .add_plugin(TypeRegistrationPlugin::default())
+            .add_plugin(FrameCountPlugin::default())
+            // Rapier Headless Requirements, same as the server
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(ScenePlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<Scene>()
+            .add_plugin(TimePlugin::default())
+            .add_plugin(RapierPhysicsPlugin::<ArenaHooks>::default())
+            .add_plugin(RngPlugin::new().with_rng_seed(0772))
+            .add_plugin(RulesPlugin)
+            .insert_resource(FixedTime::new_from_secs(TIME_STEP))
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.add_systems((goalie, magnus_effect).after(PhysicsSet::Writeback));
+            });
+
+        app.world
+            .resource_mut::<RapierConfiguration>()
+            .timestep_mode = TimestepMode::Fixed {
+            dt: TIME_STEP,
+            substeps: 1,
+        };
+
+        let now = Instant::now();
+        app.insert_resource(TimeUpdateStrategy::ManualInstant(now));
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        field::spawn_ground(&mut commands);
+        field::spawn_goal(&mut commands, Arena::default());
+        let goalie = spawn_goalie(&mut commands, goalie_mode);
+        let ball = commands.spawn(field::ball_body(Arena::default())).id();
+        queue.apply(&mut app.world);
+
+        Self {
+            app,
+            now,
+            ball,
+            goalie,
+        }
+    }
+
+    /// Advance the simulation by one `TIME_STEP`
+    pub fn step(&mut self) {
+        self.now += Duration::from_secs_f32(TIME_STEP);
+        self.app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(self.now));
+        self.app.update();
+    }
+
+    /// Hands the goalie to a "user", see `GoalieControl`
+    pub fn control_goalie(&mut self, control: GoalieControl) {
+        self.app.world.entity_mut(self.goalie).insert(control);
+    }
+
+    pub fn goalie_x(&self) -> f32 {
+        self.app
+            .world
+            .get::<Transform>(self.goalie)
+            .expect("goalie exists")
+            .translation
+            .x
+    }
+
+    pub fn ball_translation(&self) -> Vec3 {
+        self.app
+            .world
+            .get::<Transform>(self.ball)
+            .expect("ball exists")
+            .translation
+    }
+
+    /// Steps until the ball has dropped from `BALL_START` and can be kicked
+    pub fn settle_ball(&mut self) {
+        let kick_model = self.app.world.resource::<KickModel>().clone();
+        for _ in 0..MAX_STEPS {
+            let ball = self.app.world.get::<Ball>(self.ball).expect("ball exists");
+            let transform = self
+                .app
+                .world
+                .get::<Transform>(self.ball)
+                .expect("ball exists");
+            if kick_model.can_kick(ball, transform) {
+                return;
+            }
+            self.step();
+        }
+        panic!("ball never settled: {}", self.ball_translation());
+    }
+
+    /// Settles the ball, kicks it and runs the shot until the rules reset the ball.  Panics if the
+    /// shot had more than one outcome.
+    pub fn kick(&mut self, kick: &Kick) -> Outcome {
+        self.settle_ball();
+        self.drain_outcomes();
+
+        let kick_model = self.app.world.resource::<KickModel>().clone();
+        let mut ball_entity = self.app.world.entity_mut(self.ball);
+        let transform = *ball_entity.get::<Transform>().expect("ball exists");
+        let mut ext_i = *ball_entity.get::<ExternalImpulse>().expect("ball exists");
+        let mut ball = ball_entity.take::<Ball>().expect("ball exists");
+        let kicked = kick_model.kick(
+            kick.ray_normal,
+            kick.ray_point,
+            &transform,
+            &mut ball,
+            &mut ext_i,
+        );
+        ball_entity.insert((ball, ext_i));
+        assert!(kicked, "settled ball should be kickable");
+
+        let mut outcomes = Vec::new();
+        for _ in 0..MAX_STEPS {
+            self.step();
+            outcomes.extend(self.drain_outcomes());
+            if !self
+                .app
+                .world
+                .get::<Ball>(self.ball)
+                .expect("ball exists")
+                .shot
+            {
+                break;
+            }
+        }
+
+        assert!(outcomes.len() <= 1, "one outcome per shot: {outcomes:?}");
+        match outcomes.first() {
+            Some(ShotOutcomeKind::Goal) => Outcome::Goal,
+            Some(ShotOutcomeKind::DeniedGoalie) => Outcome::GoalieDeny,
+            Some(ShotOutcomeKind::DeniedFrame) => Outcome::FrameDeny,
+            None => Outcome::Miss,
+        }
+    }
+
+    pub fn run(&mut self, kicks: &[Kick]) -> Vec<Outcome> {
+        kicks.iter().map(|kick| self.kick(kick)).collect()
+    }
+
+    fn drain_outcomes(&mut self) -> Vec<ShotOutcomeKind> {
+        let ball = self.ball;
+        self.app
+            .world
+            .resource_mut::<Events<ShotOutcome>>()
+            .drain()
+            .filter(|outcome| outcome.ball == ball)
+            .map(|outcome| outcome.kind)
+            .collect()
+    }
+}
+
+fn spawn_goalie(commands: &mut Commands, mode: GoalieMode) -> Entity {
+    let (behavior, x) = match mode {
+        GoalieMode::Patrol => (GoalieBehavior::new(Difficulty::Easy), GOALIE_START.x),
+        GoalieMode::Stationary(x) => (GoalieBehavior::with_brain(Box::new(Stationary)), x),
+        GoalieMode::Difficulty(difficulty) => (GoalieBehavior::new(difficulty), GOALIE_START.x),
+    };
+
+    let translation = Vec3::new(x, GOALIE_START.y, GOALIE_START.z);
+    commands
+        .spawn(field::goalie_body(Arena::default(), translation))
+        .insert(behavior)
+        .id()
+}
@@ -0,0 +1,101 @@
+mod harness;
+
+use powerbaby_core::{
+    components::GoalieControl,
+    constants::*,
+    goalie_ai::{Difficulty, GoalieAction},
+};
+
+use harness::{GoalieMode, Kick, Outcome, Simulation};
+
+#[test]
+fn straight_shot_scores_against_stationary_goalie() {
+    // in the goal but off the ball's line
+    let mut sim = Simulation::new(GoalieMode::Stationary(1.0));
+    assert_eq!(sim.kick(&Kick::straight()), Outcome::Goal);
+}
+
+#[test]
+fn low_drive_into_goalie_is_denied() {
+    let mut sim = Simulation::new(GoalieMode::Stationary(0.0));
+    assert_eq!(sim.kick(&Kick::low_drive()), Outcome::GoalieDeny);
+}
+
+#[test]
+fn shot_at_the_post_is_denied_by_the_frame() {
+    let mut sim = Simulation::new(GoalieMode::Stationary(0.0));
+    assert_eq!(sim.kick(&Kick::post()), Outcome::FrameDeny);
+}
+
+#[test]
+fn every_shot_counts_once() {
+    let kicks = [
+        Kick::straight(),
+        Kick::low_drive(),
+        Kick::post(),
+        Kick::backwards(),
+    ];
+    for goalie_mode in [
+        GoalieMode::Patrol,
+        GoalieMode::Stationary(0.0),
+        GoalieMode::Difficulty(Difficulty::Hard),
+    ] {
+        // `kick` panics on a second outcome for the same shot
+        Simulation::new(goalie_mode).run(&kicks);
+    }
+}
+
+#[test]
+fn backwards_kick_misses() {
+    let mut sim = Simulation::new(GoalieMode::Stationary(0.0));
+    assert_eq!(sim.kick(&Kick::backwards()), Outcome::Miss);
+}
+
+#[test]
+fn kicks_are_deterministic() {
+    let kicks = [
+        Kick::straight(),
+        Kick::low_drive(),
+        Kick::straight(),
+        Kick::backwards(),
+    ];
+
+    let mut first = Simulation::new(GoalieMode::Patrol);
+    let mut second = Simulation::new(GoalieMode::Patrol);
+    assert_eq!(first.run(&kicks), second.run(&kicks));
+    assert_eq!(first.goalie_x(), second.goalie_x());
+    assert_eq!(first.ball_translation(), second.ball_translation());
+}
+
+#[test]
+fn every_difficulty_is_deterministic() {
+    let kicks = [Kick::straight(), Kick::low_drive(), Kick::straight()];
+    for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard] {
+        let mut first = Simulation::new(GoalieMode::Difficulty(difficulty));
+        let mut second = Simulation::new(GoalieMode::Difficulty(difficulty));
+        assert_eq!(first.run(&kicks), second.run(&kicks), "{difficulty:?}");
+        assert_eq!(first.goalie_x(), second.goalie_x(), "{difficulty:?}");
+    }
+}
+
+#[test]
+fn controlled_goalie_stays_within_the_patrol_bounds() {
+    let mut sim = Simulation::new(GoalieMode::Stationary(0.0));
+    sim.control_goalie(GoalieControl {
+        direction: 1.0,
+        action: None,
+    });
+    for _ in 0..(2.0 / TIME_STEP) as usize {
+        sim.step();
+    }
+    assert_eq!(sim.goalie_x(), GOALIE_PATROL_MAX_X);
+
+    sim.control_goalie(GoalieControl {
+        direction: 0.0,
+        action: Some(GoalieAction::DiveLeft),
+    });
+    for _ in 0..(GOALIE_DIVE_TIME * 0.5 / TIME_STEP) as usize {
+        sim.step();
+    }
+    assert!(sim.goalie_x() < GOALIE_PATROL_MAX_X - GOALIE_DIVE_LUNGE);
+}
@@ -4,7 +4,7 @@ use bevy::prelude::*;
 // the `bevy_main` proc_macro generates the required boilerplate for iOS and Android
 #[bevy_main]
 fn main() {
-    client::run();
+    client::run(client::ClientSettings::default());
     // App::new()
     //     .add_plugins(DefaultPlugins.set(WindowPlugin {
     //         primary_window: Some(Window {
@@ -7,22 +7,27 @@ use std::time::Duration;
 // use naia_bevy_shared::{LinkConditionerConfig, Protocol};
 use naia_bevy_shared::Protocol;
 
-pub const MAGIC_NUMBER: u16 = 0772;
-// pub const SERVER_HANDSHAKE_URL: &str = "http://192.168.0.188:14191";
-// pub const SERVER_AD_URL: &str = "http://192.168.0.188:14192";
-// pub const SERVER_HANDSHAKE_URL: &str = "http://35.184.62.109:14191";
-// pub const SERVER_HANDSHAKE_URL: &str = "https://powerbaby.ngrok.io";
-// pub const SERVER_AD_URL: &str = "http://35.184.62.109:14192";
+use primitives::ProtocolVersion;
+
+/// Bump major for any change to the wire format of existing messages or components: new fields,
+/// new enum variants, or a change to the order types are registered in, which is what naia's ids
+/// come from.  `Auth` is decoded before its version can be checked, so even appending a field to
+/// it is a major bump.  Bump minor only for messages or components appended to the end of the
+/// registration that older clients never receive.  The server only talks to clients of its own
+/// major version, see `server::version`.
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::new(4, 0, 0);
+/// Time between server ticks, every tick the server sends state updates
+pub const TICK_INTERVAL: Duration = Duration::from_millis(40);
+/// Default handshake URL for local development, deployments override it at runtime
 pub const SERVER_HANDSHAKE_URL: &str = "http://127.0.0.1:14191";
+/// Default advertised data URL for local development, see `powerbaby server --public-url`
 pub const SERVER_AD_URL: &str = "http://127.0.0.1:14192";
-// pub const SERVER_URL: &str = "http://192.168.0.188:14191";
-// pub const SERVER_URL: &str = "http://192.168.50.156:14191";
 
 // Protocol Build
 pub fn protocol() -> Protocol {
     Protocol::builder()
         // Config
-        .tick_interval(Duration::from_millis(40))
+        .tick_interval(TICK_INTERVAL)
         // .link_condition(LinkConditionerConfig::poor_condition())
         .enable_client_authoritative_entities()
         // Channels
@@ -80,20 +85,182 @@ pub mod primitives {
     use naia_bevy_shared::Serde;
     use serde::{Deserialize, Serialize};
 
+    /// Personal scores and stats are keyed by player id, see `names` for what to display
     #[derive(Default, Serialize, Deserialize)]
     pub struct Scores {
         pub personal_blue: HashMap<String, u32>,
         pub personal_pink: HashMap<String, u32>,
         pub blue_total: u32,
         pub pink_total: u32,
+        #[serde(default)]
+        pub stats: HashMap<String, PlayerStats>,
+        /// Last display name used by each player id
+        #[serde(default)]
+        pub names: HashMap<String, String>,
+        /// How the keys are laid out, `Scores::VERSION` once the server has migrated them.  0 in
+        /// files from before player ids, where the keys are display names.
+        #[serde(default)]
+        pub version: u32,
+    }
+
+    impl Scores {
+        /// Keys are player ids
+        pub const VERSION: u32 = 1;
+
+        pub fn stats_mut(&mut self, player_id: &str) -> &mut PlayerStats {
+            self.stats.entry(player_id.to_owned()).or_default()
+        }
+    }
+
+    /// Lifetime stats of a player.  `first_seen`/`last_seen` are unix timestamps in seconds.
+    #[derive(Clone, Default, PartialEq, Serde, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct PlayerStats {
+        pub kicks: u32,
+        pub goals: u32,
+        pub goalie_denies: u32,
+        pub frame_denies: u32,
+        /// Goals in a row, broken by a kick that doesn't score
+        pub current_streak: u32,
+        pub longest_streak: u32,
+        /// The last kick hasn't scored (yet)
+        pub unscored_kick: bool,
+        pub first_seen: u64,
+        pub last_seen: u64,
+    }
+
+    impl PlayerStats {
+        /// Goals per kick, from 0.0 to 1.0
+        pub fn accuracy(&self) -> f32 {
+            if self.kicks == 0 {
+                0.0
+            } else {
+                self.goals as f32 / self.kicks as f32
+            }
+        }
+
+        pub fn seen(&mut self, now: u64) {
+            if self.first_seen == 0 {
+                self.first_seen = now;
+            }
+            self.last_seen = now;
+        }
+
+        pub fn kicked(&mut self, now: u64) {
+            if self.unscored_kick {
+                self.current_streak = 0;
+            }
+            self.unscored_kick = true;
+            self.kicks += 1;
+            self.seen(now);
+        }
+
+        pub fn scored(&mut self) {
+            self.unscored_kick = false;
+            self.goals += 1;
+            self.current_streak += 1;
+            self.longest_streak = self.longest_streak.max(self.current_streak);
+        }
+
+        pub fn denied_by_goalie(&mut self) {
+            self.goalie_denies += 1;
+        }
+
+        pub fn denied_by_frame(&mut self) {
+            self.frame_denies += 1;
+        }
+
+        /// Adds up the stats of two ids that turned out to be the same player.  The streak is
+        /// taken from whichever was seen last.
+        pub fn merge(&mut self, other: &PlayerStats) {
+            if other.last_seen > self.last_seen {
+                self.current_streak = other.current_streak;
+                self.unscored_kick = other.unscored_kick;
+                self.last_seen = other.last_seen;
+            }
+            if self.first_seen == 0 || (other.first_seen != 0 && other.first_seen < self.first_seen)
+            {
+                self.first_seen = other.first_seen;
+            }
+            self.kicks += other.kicks;
+            self.goals += other.goals;
+            self.goalie_denies += other.goalie_denies;
+            self.frame_denies += other.frame_denies;
+            self.longest_streak = self.longest_streak.max(other.longest_streak);
+        }
     }
 
-    #[derive(Copy, Clone, Eq, Hash, PartialEq, Serde, Serialize, Deserialize)]
+    #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serde, Serialize, Deserialize)]
     pub enum PlayColor {
         Blue,
         Pink,
     }
 
+    #[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serde)]
+    pub struct ProtocolVersion {
+        pub major: u16,
+        pub minor: u16,
+        pub patch: u16,
+    }
+
+    impl ProtocolVersion {
+        pub const fn new(major: u16, minor: u16, patch: u16) -> Self {
+            Self {
+                major,
+                minor,
+                patch,
+            }
+        }
+    }
+
+    impl std::fmt::Display for ProtocolVersion {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+
+    /// Optional server behaviour a client can adapt to
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Serde)]
+    pub enum ServerFeature {
+        /// The server resolves kicks against the goalie the client saw, see `KeyCommand::seen_tick`
+        LagCompensation,
+        /// The client may predict its own ball, commands are replayed against server ticks
+        Prediction,
+        /// `PlayerStatsState` is sent
+        Stats,
+    }
+
+    /// Why the server turned a player away
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Serde)]
+    pub enum RejectReason {
+        VersionMismatch,
+        Unauthorized,
+        NameTaken,
+        NameInvalid,
+        ServerFull,
+        Banned,
+        Kicked,
+        GoalieTaken,
+        NoSuchRoom,
+    }
+
+    impl RejectReason {
+        /// Shown to the player
+        pub fn message(&self) -> &'static str {
+            match self {
+                RejectReason::VersionMismatch => "The game was updated, please refresh the page",
+                RejectReason::Unauthorized => "Your invite link is invalid",
+                RejectReason::NameTaken => "Someone is already playing with that name",
+                RejectReason::NameInvalid => "Please pick a different name",
+                RejectReason::ServerFull => "The game is full, try again in a bit",
+                RejectReason::Banned => "You can't join this game",
+                RejectReason::Kicked => "You were removed from the game",
+                RejectReason::GoalieTaken => "Someone is already in goal",
+                RejectReason::NoSuchRoom => "That game isn't running, check your link",
+            }
+        }
+    }
+
     #[derive(Clone, PartialEq, Serde)]
     pub struct Vec3 {
         pub x: f32,
@@ -110,12 +277,21 @@ pub mod primitives {
             }
         }
     }
+
+    impl From<Vec3> for BevyVec3 {
+        fn from(vec3: Vec3) -> Self {
+            BevyVec3::new(vec3.x, vec3.y, vec3.z)
+        }
+    }
 }
 
 pub mod messages {
-    use super::primitives::{PlayColor, Vec3};
+    use super::components::GoalieActionValue;
+    use super::primitives::{
+        PlayColor, PlayerStats, ProtocolVersion, RejectReason, ServerFeature, Vec3,
+    };
 
-    use naia_bevy_shared::{EntityProperty, Message, Protocol, ProtocolPlugin, Serde};
+    use naia_bevy_shared::{EntityProperty, Message, Protocol, ProtocolPlugin, Serde, Tick};
 
     // Plugin
     pub struct MessagesPlugin;
@@ -127,7 +303,13 @@ pub mod messages {
                 .add_message::<EntityAssignment>()
                 .add_message::<PlayerEvent>()
                 .add_message::<TotalScoreState>()
-                .add_message::<KeyCommand>();
+                .add_message::<PlayerStatsState>()
+                .add_message::<Rejected>()
+                .add_message::<ServerInfo>()
+                .add_message::<Announcement>()
+                .add_message::<Session>()
+                .add_message::<KeyCommand>()
+                .add_message::<GoalieCommand>();
         }
     }
 
@@ -136,18 +318,31 @@ pub mod messages {
         pub entity: EntityProperty,
         pub reset: bool,
         pub shoot: Option<(Vec3, Vec3)>,
+        /// The server tick the client was rendering when the command was issued.  Used by the
+        /// server for lag compensation.
+        pub seen_tick: Option<Tick>,
     }
 
     impl KeyCommand {
-        pub fn new(reset: bool, shoot: Option<(Vec3, Vec3)>) -> Self {
+        pub fn new(reset: bool, shoot: Option<(Vec3, Vec3)>, seen_tick: Option<Tick>) -> Self {
             Self {
                 entity: EntityProperty::new_empty(),
                 reset,
                 shoot,
+                seen_tick,
             }
         }
     }
 
+    /// Input of a user playing the goalie, see `Auth::goalie`.  Sent every tick.
+    #[derive(Message)]
+    pub struct GoalieCommand {
+        /// -1 towards -x, 0 stands still, 1 towards +x
+        pub direction: i8,
+        /// Dive or jump as soon as the goalie is standing
+        pub action: Option<GoalieActionValue>,
+    }
+
     #[derive(Message)]
     pub struct EntityAssignment {
         pub entity: EntityProperty,
@@ -165,19 +360,63 @@ pub mod messages {
 
     #[derive(Message)]
     pub struct Auth {
-        pub magic_number: u16,
+        pub version: ProtocolVersion,
         pub player_name: String,
         pub player_color: PlayColor,
+        /// Signed player identity, see `server::auth`.  Players without one are anonymous.
+        pub token: Option<String>,
+        /// Code of the match to join, the main room when missing.  Unknown codes create a room.
+        pub room: Option<String>,
+        /// Watch without a ball, the name and color are ignored
+        pub spectator: bool,
+        /// Play the room's goalie instead of kicking, the name and color are ignored
+        pub goalie: bool,
+        /// Token from `Session`, gets the ball back after a dropped connection
+        pub resume: Option<String>,
     }
 
     impl Auth {
         pub fn new(username: &str, player_color: PlayColor) -> Self {
             Self {
-                magic_number: super::MAGIC_NUMBER,
+                version: super::PROTOCOL_VERSION,
                 player_name: username.to_string(),
                 player_color,
+                token: None,
+                room: None,
+                spectator: false,
+                goalie: false,
+                resume: None,
+            }
+        }
+
+        pub fn spectator() -> Self {
+            Self {
+                spectator: true,
+                ..Self::new("spectator", PlayColor::Blue)
             }
         }
+
+        pub fn goalie() -> Self {
+            Self {
+                goalie: true,
+                ..Self::new("goalie", PlayColor::Blue)
+            }
+        }
+
+        pub fn with_token(mut self, token: Option<String>) -> Self {
+            self.token = token;
+            self
+        }
+
+        pub fn with_room(mut self, room: Option<String>) -> Self {
+            self.room = room;
+            self
+        }
+
+        pub fn with_resume(mut self, resume: Option<String>) -> Self {
+            self.resume = resume;
+            self
+        }
     }
 
     impl From<(String, String)> for Auth {
@@ -188,9 +427,14 @@ pub mod messages {
                 _ => PlayColor::Blue,
             };
             Self {
-                magic_number: super::MAGIC_NUMBER,
+                version: super::PROTOCOL_VERSION,
                 player_name,
                 player_color,
+                token: None,
+                room: None,
+                spectator: false,
+                goalie: false,
+                resume: None,
             }
         }
     }
@@ -253,12 +497,48 @@ pub mod messages {
         pub blue: u32,
         pub pink: u32,
     }
+
+    /// Sent on connect, before anything else
+    #[derive(Message)]
+    pub struct ServerInfo {
+        pub version: ProtocolVersion,
+        pub features: Vec<ServerFeature>,
+    }
+
+    /// Naia's rejections can't carry a reason, so rejected players are accepted, sent this and
+    /// disconnected shortly after
+    #[derive(Message)]
+    pub struct Rejected {
+        pub reason: RejectReason,
+    }
+
+    /// A notice from the server operator shown to everyone, an empty text clears it
+    #[derive(Message)]
+    pub struct Announcement {
+        pub text: String,
+    }
+
+    /// Sent with the ball assignment.  Reconnecting with it in `Auth::resume` before the server's
+    /// grace period runs out gets the same ball back.
+    #[derive(Message)]
+    pub struct Session {
+        pub token: String,
+    }
+
+    /// Sent to a player whenever their own stats change
+    #[derive(Message)]
+    pub struct PlayerStatsState {
+        pub stats: PlayerStats,
+    }
 }
 
 pub mod components {
-    use super::primitives::PlayColor;
+    use super::{
+        primitives::PlayColor,
+        quantized::{QuantizedPosition, QuantizedRotation, QuantizedVelocity},
+    };
 
-    use bevy::prelude::{Component, Transform, Vec3};
+    use bevy::prelude::{Component, Quat, Transform, Vec3};
     use bevy_rapier3d::prelude::*;
     use naia_bevy_shared::{Property, Protocol, ProtocolPlugin, Replicate, Serde};
 
@@ -270,7 +550,8 @@ pub mod components {
             protocol
                 .add_component::<RepPhysics>()
                 .add_component::<Player>()
-                .add_component::<EntityKind>();
+                .add_component::<EntityKind>()
+                .add_component::<GoalieState>();
         }
     }
 
@@ -278,124 +559,80 @@ pub mod components {
         fn update_with(&mut self, with: T);
     }
 
-    /// Protocol component for Naia that stores the rapier data
+    /// Protocol component for Naia that stores the rapier data.  Values are quantized, see
+    /// `quantized`, and each property is only written when its quantized value changes so naia
+    /// only sends what actually moved.
     #[derive(Component, Replicate)]
     pub struct RepPhysics {
-        // translation
-        pub translation_x: Property<f32>,
-        pub translation_y: Property<f32>,
-        pub translation_z: Property<f32>,
-        // rotation
-        pub rotation_x: Property<f32>,
-        pub rotation_y: Property<f32>,
-        pub rotation_z: Property<f32>,
-        pub rotation_w: Property<f32>,
-        // // scale
-        // pub scale_x: Property<f32>,
-        // pub scale_y: Property<f32>,
-        // pub scale_z: Property<f32>,
-        // linvel
-        pub linvel_x: Property<f32>,
-        pub linvel_y: Property<f32>,
-        pub linvel_z: Property<f32>,
-        // angvel
-        pub angvel_x: Property<f32>,
-        pub angvel_y: Property<f32>,
-        pub angvel_z: Property<f32>,
+        pub position: Property<QuantizedPosition>,
+        pub rotation: Property<QuantizedRotation>,
+        /// `None` for entities clients don't need the velocity of, like the kinematic goalie
+        pub velocity: Property<Option<QuantizedVelocity>>,
     }
 
     impl RepPhysics {
         pub fn new_with(transform: &Transform, velocity: &Velocity) -> Self {
             Self::new_complete(
-                transform.translation.x,
-                transform.translation.y,
-                transform.translation.z,
-                transform.rotation.x,
-                transform.rotation.y,
-                transform.rotation.z,
-                transform.rotation.w,
-                velocity.linvel.x,
-                velocity.linvel.y,
-                velocity.linvel.z,
-                velocity.angvel.x,
-                velocity.angvel.y,
-                velocity.angvel.z,
+                QuantizedPosition::new(transform.translation),
+                QuantizedRotation::new(transform.rotation),
+                Some(QuantizedVelocity::new(velocity)),
             )
         }
+
+        pub fn new_without_velocity(transform: &Transform) -> Self {
+            Self::new_complete(
+                QuantizedPosition::new(transform.translation),
+                QuantizedRotation::new(transform.rotation),
+                None,
+            )
+        }
+
+        pub fn translation(&self) -> Vec3 {
+            (*self.position).get()
+        }
+
+        pub fn rotation(&self) -> Quat {
+            (*self.rotation).get()
+        }
+
+        pub fn velocity(&self) -> Option<Velocity> {
+            (*self.velocity).as_ref().map(QuantizedVelocity::get)
+        }
     }
 
     impl UpdateWith<(&Transform, &Velocity)> for RepPhysics {
-        fn update_with(&mut self, (&transform, &velocity): (&Transform, &Velocity)) {
-            // if *self.translation_x == transform.translation.x {
-            //     *self.translation_x = transform.translation.x;
-            // }
-            // if *self.translation_y == transform.translation.y {
-            //     *self.translation_y = transform.translation.y;
-            // }
-            // if *self.translation_z == transform.translation.z {
-            //     *self.translation_z = transform.translation.z;
-            // }
-            //
-            // if *self.rotation_x == transform.rotation.x {
-            //     *self.rotation_x = transform.rotation.x;
-            // }
-            // if *self.rotation_y == transform.rotation.y {
-            //     *self.rotation_y = transform.rotation.y;
-            // }
-            // if *self.rotation_z == transform.rotation.z {
-            //     *self.rotation_z = transform.rotation.z;
-            // }
-            // if *self.rotation_w == transform.rotation.w {
-            //     *self.rotation_w = transform.rotation.w;
-            // }
-            *self.translation_x = transform.translation.x;
-            *self.translation_y = transform.translation.y;
-            *self.translation_z = transform.translation.z;
-
-            *self.rotation_x = transform.rotation.x;
-            *self.rotation_y = transform.rotation.y;
-            *self.rotation_z = transform.rotation.z;
-            *self.rotation_w = transform.rotation.w;
-
-            // *self.scale_x = transform.scale.x;
-            // *self.scale_y = transform.scale.y;
-            // *self.scale_z = transform.scale.z;
-
-            *self.linvel_x = velocity.linvel.x;
-            *self.linvel_y = velocity.linvel.y;
-            *self.linvel_z = velocity.linvel.z;
-            *self.angvel_x = velocity.angvel.x;
-            *self.angvel_y = velocity.angvel.y;
-            *self.angvel_z = velocity.angvel.z;
+        fn update_with(&mut self, (transform, velocity): (&Transform, &Velocity)) {
+            let position = QuantizedPosition::new(transform.translation);
+            if *self.position != position {
+                *self.position = position;
+            }
+
+            let rotation = QuantizedRotation::new(transform.rotation);
+            if *self.rotation != rotation {
+                *self.rotation = rotation;
+            }
+
+            if self.velocity.is_some() {
+                let velocity = Some(QuantizedVelocity::new(velocity));
+                if *self.velocity != velocity {
+                    *self.velocity = velocity;
+                }
+            }
         }
     }
 
     impl UpdateWith<&RepPhysics> for Transform {
         fn update_with(&mut self, physics_properties: &RepPhysics) {
-            self.translation.x = *physics_properties.translation_x;
-            self.translation.y = *physics_properties.translation_y;
-            self.translation.z = *physics_properties.translation_z;
-
-            self.rotation.x = *physics_properties.rotation_x;
-            self.rotation.y = *physics_properties.rotation_y;
-            self.rotation.z = *physics_properties.rotation_z;
-            self.rotation.w = *physics_properties.rotation_w;
-
-            // self.scale.x = *physics_properties.scale_x;
-            // self.scale.y = *physics_properties.scale_y;
-            // self.scale.z = *physics_properties.scale_z;
+            self.translation = physics_properties.translation();
+            self.rotation = physics_properties.rotation();
         }
     }
 
     impl UpdateWith<&RepPhysics> for Velocity {
         fn update_with(&mut self, physics_properties: &RepPhysics) {
-            self.linvel.x = *physics_properties.linvel_x;
-            self.linvel.y = *physics_properties.linvel_y;
-            self.linvel.z = *physics_properties.linvel_z;
-
-            self.angvel.x = *physics_properties.angvel_x;
-            self.angvel.y = *physics_properties.angvel_y;
-            self.angvel.z = *physics_properties.angvel_z;
+            if let Some(velocity) = physics_properties.velocity() {
+                *self = velocity;
+            }
         }
     }
 
@@ -450,6 +687,192 @@ pub mod components {
         Goalie,
         Ball,
     }
+
+    /// What the goalie is doing, clients pose the goalie model from it.  The collider's pose is
+    /// already in `RepPhysics`.
+    #[derive(Component, Replicate)]
+    pub struct GoalieState {
+        pub action: Property<GoalieActionValue>,
+    }
+
+    impl GoalieState {
+        pub fn standing() -> Self {
+            Self::new_complete(GoalieActionValue::Stand)
+        }
+    }
+
+    /// Mirrors `core::goalie_ai::GoalieAction`
+    #[derive(Serde, PartialEq, Clone, Copy, Debug)]
+    pub enum GoalieActionValue {
+        Stand,
+        DiveLeft,
+        DiveRight,
+        Jump,
+    }
+}
+
+/// Compact encodings for `components::RepPhysics`.  Everything is mapped linearly onto an
+/// unsigned integer of a fixed amount of bits, values outside the range are clamped.
+pub mod quantized {
+    use bevy::prelude::{Quat, Vec3};
+    use bevy_rapier3d::prelude::Velocity;
+    use naia_bevy_shared::{Serde, UnsignedInteger};
+
+    /// Bounds of the playing field, the ground is 200x200 around the origin
+    pub const FIELD_MIN: Vec3 = Vec3::new(-100.0, -1.0, -100.0);
+    pub const FIELD_MAX: Vec3 = Vec3::new(100.0, 63.0, 100.0);
+    /// ~3mm steps along the ground
+    pub const XZ_BITS: u8 = 16;
+    /// ~4mm steps in the air
+    pub const Y_BITS: u8 = 14;
+
+    /// Bits per quaternion component, the largest one isn't sent
+    pub const ROTATION_BITS: u8 = 10;
+    /// The three smallest components of a unit quaternion are within this
+    const ROTATION_MAX: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    pub const VELOCITY_BITS: u8 = 12;
+    /// m/s, a hard kick is ~30
+    pub const LINVEL_MAX: f32 = 50.0;
+    /// rad/s
+    pub const ANGVEL_MAX: f32 = 100.0;
+
+    /// An even amount of steps so the middle of a symmetric range, e.g. a velocity of 0, is
+    /// exactly representable.  The largest value of `bits` is unused.
+    fn steps(bits: u8) -> f32 {
+        ((1u32 << bits) - 2) as f32
+    }
+
+    fn quantize(value: f32, min: f32, max: f32, bits: u8) -> u32 {
+        ((value.clamp(min, max) - min) / (max - min) * steps(bits)).round() as u32
+    }
+
+    fn dequantize(value: i128, min: f32, max: f32, bits: u8) -> f32 {
+        min + value as f32 / steps(bits) * (max - min)
+    }
+
+    #[derive(Serde, Clone, PartialEq, Debug)]
+    pub struct QuantizedPosition {
+        x: UnsignedInteger<XZ_BITS>,
+        y: UnsignedInteger<Y_BITS>,
+        z: UnsignedInteger<XZ_BITS>,
+    }
+
+    impl QuantizedPosition {
+        pub fn new(v: Vec3) -> Self {
+            Self {
+                x: UnsignedInteger::new(quantize(v.x, FIELD_MIN.x, FIELD_MAX.x, XZ_BITS)),
+                y: UnsignedInteger::new(quantize(v.y, FIELD_MIN.y, FIELD_MAX.y, Y_BITS)),
+                z: UnsignedInteger::new(quantize(v.z, FIELD_MIN.z, FIELD_MAX.z, XZ_BITS)),
+            }
+        }
+
+        pub fn get(&self) -> Vec3 {
+            Vec3::new(
+                dequantize(self.x.get(), FIELD_MIN.x, FIELD_MAX.x, XZ_BITS),
+                dequantize(self.y.get(), FIELD_MIN.y, FIELD_MAX.y, Y_BITS),
+                dequantize(self.z.get(), FIELD_MIN.z, FIELD_MAX.z, XZ_BITS),
+            )
+        }
+    }
+
+    /// "Smallest three" encoding.  `q` and `-q` are the same rotation so the largest component
+    /// can always be made positive and recomputed from the other three.
+    #[derive(Serde, Clone, PartialEq, Debug)]
+    pub struct QuantizedRotation {
+        largest: UnsignedInteger<2>,
+        a: UnsignedInteger<ROTATION_BITS>,
+        b: UnsignedInteger<ROTATION_BITS>,
+        c: UnsignedInteger<ROTATION_BITS>,
+    }
+
+    impl QuantizedRotation {
+        pub fn new(q: Quat) -> Self {
+            let q = q.normalize().to_array();
+            let largest = (0..4)
+                .max_by(|&i, &j| q[i].abs().total_cmp(&q[j].abs()))
+                .expect("4 components. qed");
+            let sign = q[largest].signum();
+            let mut rest = (0..4).filter(|&i| i != largest).map(|i| {
+                UnsignedInteger::new(quantize(
+                    q[i] * sign,
+                    -ROTATION_MAX,
+                    ROTATION_MAX,
+                    ROTATION_BITS,
+                ))
+            });
+
+            Self {
+                largest: UnsignedInteger::new(largest as u8),
+                a: rest.next().expect("3 components. qed"),
+                b: rest.next().expect("3 components. qed"),
+                c: rest.next().expect("3 components. qed"),
+            }
+        }
+
+        pub fn get(&self) -> Quat {
+            let largest = self.largest.get() as usize;
+            let mut rest = [&self.a, &self.b, &self.c]
+                .into_iter()
+                .map(|v| dequantize(v.get(), -ROTATION_MAX, ROTATION_MAX, ROTATION_BITS));
+
+            let mut q = [0.0; 4];
+            for (i, component) in q.iter_mut().enumerate() {
+                if i != largest {
+                    *component = rest.next().expect("3 components. qed");
+                }
+            }
+            let sum_squares: f32 = q.iter().map(|v| v * v).sum();
+            q[largest] = (1.0 - sum_squares).max(0.0).sqrt();
+            Quat::from_array(q).normalize()
+        }
+    }
+
+    #[derive(Serde, Clone, PartialEq, Debug)]
+    pub struct QuantizedVelocity {
+        linvel_x: UnsignedInteger<VELOCITY_BITS>,
+        linvel_y: UnsignedInteger<VELOCITY_BITS>,
+        linvel_z: UnsignedInteger<VELOCITY_BITS>,
+        angvel_x: UnsignedInteger<VELOCITY_BITS>,
+        angvel_y: UnsignedInteger<VELOCITY_BITS>,
+        angvel_z: UnsignedInteger<VELOCITY_BITS>,
+    }
+
+    impl QuantizedVelocity {
+        pub fn new(velocity: &Velocity) -> Self {
+            let lin = |v| UnsignedInteger::new(quantize(v, -LINVEL_MAX, LINVEL_MAX, VELOCITY_BITS));
+            let ang = |v| UnsignedInteger::new(quantize(v, -ANGVEL_MAX, ANGVEL_MAX, VELOCITY_BITS));
+            Self {
+                linvel_x: lin(velocity.linvel.x),
+                linvel_y: lin(velocity.linvel.y),
+                linvel_z: lin(velocity.linvel.z),
+                angvel_x: ang(velocity.angvel.x),
+                angvel_y: ang(velocity.angvel.y),
+                angvel_z: ang(velocity.angvel.z),
+            }
+        }
+
+        pub fn get(&self) -> Velocity {
+            let lin = |v: &UnsignedInteger<VELOCITY_BITS>| {
+                dequantize(v.get(), -LINVEL_MAX, LINVEL_MAX, VELOCITY_BITS)
+            };
+            let ang = |v: &UnsignedInteger<VELOCITY_BITS>| {
+                dequantize(v.get(), -ANGVEL_MAX, ANGVEL_MAX, VELOCITY_BITS)
+            };
+            Velocity {
+                linvel: Vec3::new(
+                    lin(&self.linvel_x),
+                    lin(&self.linvel_y),
+                    lin(&self.linvel_z),
+                ),
+                angvel: Vec3::new(
+                    ang(&self.angvel_x),
+                    ang(&self.angvel_y),
+                    ang(&self.angvel_z),
+                ),
+            }
+        }
+    }
 }
 
 mod channel {
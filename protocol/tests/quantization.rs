@@ -0,0 +1,89 @@
+use powerbaby_protocol::quantized::{
+    QuantizedPosition, QuantizedRotation, QuantizedVelocity, FIELD_MAX, FIELD_MIN, XZ_BITS, Y_BITS,
+};
+
+use bevy::prelude::{Quat, Vec3};
+use bevy_rapier3d::prelude::Velocity;
+use naia_bevy_shared::Serde;
+
+/// What `RepPhysics` used to be: 13 `f32` properties
+fn unquantized_bits() -> u32 {
+    13 * 0.0f32.bit_length()
+}
+
+fn quantized_bits(with_velocity: bool) -> u32 {
+    let position = QuantizedPosition::new(Vec3::new(1.0, 2.0, 40.0));
+    let rotation = QuantizedRotation::new(Quat::from_rotation_y(1.0));
+    let velocity = with_velocity.then(|| QuantizedVelocity::new(&Velocity::zero()));
+    position.bit_length() + rotation.bit_length() + velocity.bit_length()
+}
+
+/// Compares the encoded size of the properties only.  A real update also carries naia's
+/// headers and only the properties that changed, so this isn't the size of a packet.
+#[test]
+fn quantized_properties_are_smaller() {
+    let before = unquantized_bits();
+    assert!(quantized_bits(true) * 2 < before);
+    assert!(quantized_bits(false) * 4 < before);
+}
+
+#[test]
+fn position_roundtrip() {
+    let xz_step = (FIELD_MAX.x - FIELD_MIN.x) / ((1u32 << XZ_BITS) - 1) as f32;
+    let y_step = (FIELD_MAX.y - FIELD_MIN.y) / ((1u32 << Y_BITS) - 1) as f32;
+    // rounding is off by half a step, plus float error
+    let tolerance = xz_step.max(y_step);
+    for v in [
+        Vec3::new(0.0, 1.1, 42.0),
+        Vec3::new(-1.5, 0.6, 32.8),
+        Vec3::new(3.3, 7.25, 12.1),
+        FIELD_MIN,
+        FIELD_MAX,
+    ] {
+        let decoded = QuantizedPosition::new(v).get();
+        assert!(
+            decoded.abs_diff_eq(v, tolerance),
+            "{v} decoded as {decoded}"
+        );
+    }
+}
+
+#[test]
+fn position_is_clamped() {
+    let decoded = QuantizedPosition::new(Vec3::new(-500.0, 500.0, 0.0)).get();
+    assert_eq!(decoded.x, FIELD_MIN.x);
+    assert_eq!(decoded.y, FIELD_MAX.y);
+}
+
+#[test]
+fn rotation_roundtrip() {
+    for q in [
+        Quat::IDENTITY,
+        Quat::from_rotation_y(4.71239),
+        Quat::from_rotation_x(-2.0) * Quat::from_rotation_z(0.3),
+        -Quat::from_euler(bevy::prelude::EulerRot::XYZ, 0.1, 2.0, -1.2),
+    ] {
+        let decoded = QuantizedRotation::new(q).get();
+        assert!((decoded.length() - 1.0).abs() < 1e-5);
+        // q and -q are the same rotation
+        assert!(decoded.dot(q).abs() > 0.9999, "{q} decoded as {decoded}");
+    }
+}
+
+#[test]
+fn velocity_roundtrip() {
+    let velocity = Velocity {
+        linvel: Vec3::new(1.0, 4.5, -28.0),
+        angvel: Vec3::new(0.0, -12.0, 3.0),
+    };
+    let decoded = QuantizedVelocity::new(&velocity).get();
+    assert!(decoded.linvel.abs_diff_eq(velocity.linvel, 0.05));
+    assert!(decoded.angvel.abs_diff_eq(velocity.angvel, 0.1));
+}
+
+#[test]
+fn zero_velocity_is_exact() {
+    // resting balls are extrapolated with their velocity, it has to stay put
+    let decoded = QuantizedVelocity::new(&Velocity::zero()).get();
+    assert_eq!(decoded, Velocity::zero());
+}
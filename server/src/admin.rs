@@ -0,0 +1,390 @@
+//! Operator console.  Lines typed on the server's stdin are parsed into `AdminCommand`s and run
+//! by `admin_commands` on the game loop, replies go to stdout.  Enabled with
+//! `ServerSettings::admin_console`.
+use crate::{
+    auth::PlayerId, names, registry::PlayerRegistry, rooms::RoomManager, sessions::Sessions,
+    Global, ScoreFlush, REJECT_DISCONNECT_DELAY,
+};
+use core::{components::GoalieBehavior, goalie_ai::Difficulty};
+use protocol::{
+    channels::GameStateChannel,
+    components::Player,
+    messages::{Announcement, Rejected, TotalScoreState},
+    primitives::{PlayColor, RejectReason, Scores},
+};
+
+use std::{
+    io::BufRead,
+    str::FromStr,
+    sync::{mpsc, Mutex},
+};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::RapierConfiguration;
+use naia_bevy_server::{Server, UserKey};
+
+pub const HELP: &str = "commands:
+  list                       connected players and spectators
+  kick <name|id>             disconnect a player
+  ban <name|id>              disconnect a player and turn them away until restart
+  unban <name|id>
+  scores                     team totals of every room
+  scores reset               zero the persisted team totals and personal scores
+  scores <blue|pink> <n>     set a persisted team total
+  announce <text>            show a notice to everyone, `announce` alone clears it
+  pause / resume             freeze physics and kicks
+  goalie <room> <level>      easy, normal or hard, until the room closes
+  help";
+
+/// Shown to everyone while the game is paused
+const PAUSED_NOTICE: &str = "Paused, back in a moment";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AdminCommand {
+    List,
+    Kick(String),
+    Ban(String),
+    Unban(String),
+    Scores,
+    ResetScores,
+    SetTotal(PlayColor, u32),
+    Announce(String),
+    Pause,
+    Resume,
+    Goalie(String, Difficulty),
+    Help,
+}
+
+impl FromStr for AdminCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (command, rest) = line
+            .split_once(char::is_whitespace)
+            .map(|(command, rest)| (command, rest.trim()))
+            .unwrap_or((line, ""));
+        let who = || {
+            if rest.is_empty() {
+                Err(format!("{command}: expected a player name or id"))
+            } else {
+                Ok(rest.to_owned())
+            }
+        };
+        match (command, rest) {
+            ("list", "") => Ok(AdminCommand::List),
+            ("kick", _) => who().map(AdminCommand::Kick),
+            ("ban", _) => who().map(AdminCommand::Ban),
+            ("unban", _) => who().map(AdminCommand::Unban),
+            ("scores", "") => Ok(AdminCommand::Scores),
+            ("scores", "reset") => Ok(AdminCommand::ResetScores),
+            ("scores", _) => {
+                let (team, total) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| format!("{line}: expected `scores <blue|pink> <total>`"))?;
+                let color = match team {
+                    "blue" => PlayColor::Blue,
+                    "pink" => PlayColor::Pink,
+                    _ => return Err(format!("{team}: expected blue or pink")),
+                };
+                let total = total
+                    .trim()
+                    .parse()
+                    .map_err(|err| format!("{total}: {err}"))?;
+                Ok(AdminCommand::SetTotal(color, total))
+            }
+            ("announce", _) => Ok(AdminCommand::Announce(rest.to_owned())),
+            ("pause", "") => Ok(AdminCommand::Pause),
+            ("resume", "") => Ok(AdminCommand::Resume),
+            ("goalie", _) => {
+                let (room, difficulty) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| format!("{line}: expected `goalie <room> <level>`"))?;
+                Ok(AdminCommand::Goalie(
+                    room.to_owned(),
+                    difficulty.trim().parse()?,
+                ))
+            }
+            ("help", "") => Ok(AdminCommand::Help),
+            _ => Err(format!("{line}: unknown command, try `help`")),
+        }
+    }
+}
+
+/// Lines from stdin.  They are read on their own thread so the game loop never blocks.
+#[derive(Resource)]
+pub struct AdminConsole(Mutex<mpsc::Receiver<String>>);
+
+impl AdminConsole {
+    pub fn stdin() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self(Mutex::new(receiver))
+    }
+}
+
+/// Set by `pause`.  Physics, goalies and the rules stop and kicks are dropped until `resume`.
+#[derive(Resource, Default)]
+pub struct Paused(pub bool);
+
+impl Paused {
+    /// Stops or restarts rapier along with the systems gated on `not_paused`
+    pub fn set(&mut self, rapier_config: &mut RapierConfiguration, pause: bool) {
+        self.0 = pause;
+        rapier_config.physics_pipeline_active = !pause;
+    }
+
+    /// For users connecting while paused
+    pub fn notice(&self) -> Option<Announcement> {
+        self.0.then(|| Announcement {
+            text: PAUSED_NOTICE.to_owned(),
+        })
+    }
+}
+
+/// Run condition for everything that moves the game along
+pub fn not_paused(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+pub fn admin_commands(
+    console: Res<AdminConsole>,
+    mut server: Server,
+    mut global: ResMut<Global>,
+    registry: Res<PlayerRegistry>,
+    mut rooms: ResMut<RoomManager>,
+    mut paused: ResMut<Paused>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut score_flush: ResMut<ScoreFlush>,
+    mut sessions: ResMut<Sessions>,
+    player_query: Query<(&Player, &PlayerId)>,
+    mut goalie_query: Query<&mut GoalieBehavior>,
+) {
+    let lines: Vec<String> = console.0.lock().unwrap().try_iter().collect();
+    for line in lines.iter().filter(|line| !line.trim().is_empty()) {
+        let admin_command = match line.parse() {
+            Ok(admin_command) => admin_command,
+            Err(err) => {
+                println!("{err}");
+                continue;
+            }
+        };
+        info!("admin: {}", line.trim());
+        match admin_command {
+            AdminCommand::List => {
+                for (user_key, entity) in registry.players() {
+                    let Ok((player, player_id)) = player_query.get(entity) else {
+                        continue;
+                    };
+                    let room = rooms
+                        .of_user(&user_key)
+                        .map_or("-", |room| room.code.as_str());
+                    let color = match *player.color {
+                        PlayColor::Blue => "blue",
+                        PlayColor::Pink => "pink",
+                    };
+                    println!(
+                        "{} ({}) {} in {} from {}",
+                        *player.name,
+                        player_id.0,
+                        color,
+                        room,
+                        server.user(&user_key).address()
+                    );
+                }
+                println!(
+                    "{} players, {} spectators",
+                    registry.player_count(),
+                    registry.spectator_count()
+                );
+            }
+            AdminCommand::Kick(who) => match find_player(&registry, &player_query, &who) {
+                Some((user_key, _)) => {
+                    sessions.end(&user_key);
+                    reject(&mut server, &mut global, user_key, RejectReason::Kicked);
+                    println!("kicked {who}");
+                }
+                None => println!("{who}: not playing"),
+            },
+            AdminCommand::Ban(who) => {
+                let player_id = match find_player(&registry, &player_query, &who) {
+                    Some((user_key, player_id)) => {
+                        sessions.end(&user_key);
+                        reject(&mut server, &mut global, user_key, RejectReason::Banned);
+                        player_id
+                    }
+                    None => match offline_player(&global.scores, &who) {
+                        Ok(player_id) => player_id,
+                        Err(err) => {
+                            println!("{err}");
+                            continue;
+                        }
+                    },
+                };
+                println!("banned {}", player_id.0);
+                global.banned.insert(player_id);
+            }
+            AdminCommand::Unban(who) => match offline_player(&global.scores, &who) {
+                Ok(player_id) => {
+                    if global.banned.remove(&player_id) {
+                        println!("unbanned {}", player_id.0);
+                    } else {
+                        println!("{}: not banned", player_id.0);
+                    }
+                }
+                Err(err) => println!("{err}"),
+            },
+            AdminCommand::Scores => {
+                println!(
+                    "persisted: blue {} pink {}",
+                    global.scores.blue_total, global.scores.pink_total
+                );
+                for room in rooms.iter() {
+                    println!(
+                        "{}: blue {} pink {}, {} users",
+                        room.code,
+                        room.blue_total,
+                        room.pink_total,
+                        room.users.len()
+                    );
+                }
+            }
+            AdminCommand::ResetScores => {
+                global.scores.blue_total = 0;
+                global.scores.pink_total = 0;
+                global.scores.personal_blue.clear();
+                global.scores.personal_pink.clear();
+                sync_main_room(&mut server, &global, &mut rooms);
+                score_flush.dirty = true;
+                println!("scores reset");
+            }
+            AdminCommand::SetTotal(color, total) => {
+                match color {
+                    PlayColor::Blue => global.scores.blue_total = total,
+                    PlayColor::Pink => global.scores.pink_total = total,
+                }
+                sync_main_room(&mut server, &global, &mut rooms);
+                score_flush.dirty = true;
+                println!(
+                    "blue {} pink {}",
+                    global.scores.blue_total, global.scores.pink_total
+                );
+            }
+            AdminCommand::Announce(text) => {
+                announce(&mut server, &text);
+                println!("sent to {} users", server.user_keys().len());
+            }
+            AdminCommand::Pause => {
+                set_paused(&mut server, &mut paused, &mut rapier_config, true);
+                println!("paused");
+            }
+            AdminCommand::Resume => {
+                set_paused(&mut server, &mut paused, &mut rapier_config, false);
+                println!("resumed");
+            }
+            AdminCommand::Goalie(code, difficulty) => match rooms.get_mut(&code) {
+                Some(room) => {
+                    room.difficulty = difficulty;
+                    // only the brain, a dive in progress plays out
+                    if let Ok(mut behavior) = goalie_query.get_mut(room.goalie_entity) {
+                        behavior.brain = difficulty.brain();
+                    }
+                    println!("{code}: {difficulty:?} goalie");
+                }
+                None => println!("{code}: no such room"),
+            },
+            AdminCommand::Help => println!("{HELP}"),
+        }
+    }
+}
+
+/// Matches the display name (ignoring case and spacing like `names` does) or the player id
+fn find_player(
+    registry: &PlayerRegistry,
+    player_query: &Query<(&Player, &PlayerId)>,
+    who: &str,
+) -> Option<(UserKey, PlayerId)> {
+    let folded = names::fold(who);
+    registry.players().find_map(|(user_key, entity)| {
+        let (player, player_id) = player_query.get(entity).ok()?;
+        (names::fold(&player.name) == folded || player_id.0 == who)
+            .then(|| (user_key, player_id.clone()))
+    })
+}
+
+/// Ids are taken as they are.  Names are looked up in the names of known players, or else are
+/// what an anonymous player with that name would get.
+pub fn offline_player(scores: &Scores, who: &str) -> Result<PlayerId, String> {
+    if let Some(player_id) = PlayerId::parse(who) {
+        return Ok(player_id);
+    }
+    let folded = names::fold(who);
+    let mut known: Vec<&String> = scores
+        .names
+        .iter()
+        .filter(|(_, name)| names::fold(name) == folded)
+        .map(|(player_id, _)| player_id)
+        .collect();
+    match known.len() {
+        0 => Ok(PlayerId::anonymous(who)),
+        1 => Ok(PlayerId(known[0].clone())),
+        _ => {
+            known.sort();
+            let ids: Vec<&str> = known.iter().map(|id| id.as_str()).collect();
+            Err(format!(
+                "{who}: several players, use one of {}",
+                ids.join(", ")
+            ))
+        }
+    }
+}
+
+/// Tells the user why and disconnects them once the message had time to arrive
+fn reject(server: &mut Server, global: &mut Global, user_key: UserKey, reason: RejectReason) {
+    server.send_message::<GameStateChannel, Rejected>(&user_key, &Rejected { reason });
+    global.rejected.push((user_key, REJECT_DISCONNECT_DELAY));
+}
+
+fn set_paused(
+    server: &mut Server,
+    paused: &mut Paused,
+    rapier_config: &mut RapierConfiguration,
+    pause: bool,
+) {
+    paused.set(rapier_config, pause);
+    let notice = paused
+        .notice()
+        .map(|notice| notice.text)
+        .unwrap_or_default();
+    announce(server, &notice);
+}
+
+fn announce(server: &mut Server, text: &str) {
+    let message = Announcement {
+        text: text.to_owned(),
+    };
+    for user_key in server.user_keys() {
+        server.send_message::<GameStateChannel, Announcement>(&user_key, &message);
+    }
+}
+
+/// The main room's tally shows the persisted totals
+fn sync_main_room(server: &mut Server, global: &Global, rooms: &mut RoomManager) {
+    let main = rooms.main_mut();
+    main.blue_total = global.scores.blue_total;
+    main.pink_total = global.scores.pink_total;
+    let message = TotalScoreState {
+        blue: main.blue_total,
+        pink: main.pink_total,
+    };
+    main.broadcast::<GameStateChannel, TotalScoreState>(server, &message);
+}
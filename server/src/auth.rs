@@ -0,0 +1,144 @@
+//! Player identity.
+//!
+//! `Auth` messages carry a display name and an optional token.  An `Authenticator` turns them into
+//! a stable `PlayerId` that scores and stats are keyed by, or rejects the connection.
+//!
+//! Ids are namespaced by where they come from: `anon:<folded name>` for players without a token
+//! and `id:<id>` for token holders, so no name can pass for a token holder.
+use crate::names;
+use protocol::{messages::Auth, primitives::Scores};
+
+use bevy::prelude::*;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Stable player identity, never shown to players
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PlayerId(pub String);
+
+const ANONYMOUS_PREFIX: &str = "anon:";
+const TOKEN_PREFIX: &str = "id:";
+
+impl PlayerId {
+    /// Case and spacing don't matter, see `names::fold`
+    pub fn anonymous(player_name: &str) -> Self {
+        PlayerId(format!("{ANONYMOUS_PREFIX}{}", names::fold(player_name)))
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.0.starts_with(ANONYMOUS_PREFIX)
+    }
+
+    /// An id typed by an operator, `None` if it isn't namespaced like the ids players get
+    pub fn parse(id: &str) -> Option<Self> {
+        [ANONYMOUS_PREFIX, TOKEN_PREFIX]
+            .iter()
+            .any(|prefix| id.starts_with(prefix))
+            .then(|| PlayerId(id.to_owned()))
+    }
+}
+
+pub trait Authenticator: Send + Sync {
+    /// `None` rejects the connection
+    fn authenticate(&self, auth: &Auth) -> Option<PlayerId>;
+}
+
+#[derive(Resource)]
+pub struct Authentication(pub Box<dyn Authenticator>);
+
+/// Anyone can play as anyone, the id is the display name
+pub struct Anonymous;
+
+impl Authenticator for Anonymous {
+    fn authenticate(&self, auth: &Auth) -> Option<PlayerId> {
+        Some(PlayerId::anonymous(&auth.player_name))
+    }
+}
+
+/// Tokens are `<player id>.<hex HMAC-SHA256 of the player id>` signed with a secret shared with
+/// whatever hands out the game links.  Verified ids are prefixed with `id:`.
+pub struct HmacAuthenticator {
+    secret: Vec<u8>,
+    /// Let players without a token in as `Anonymous`
+    pub allow_anonymous: bool,
+}
+
+impl HmacAuthenticator {
+    pub fn new(secret: impl Into<Vec<u8>>, allow_anonymous: bool) -> Self {
+        Self {
+            secret: secret.into(),
+            allow_anonymous,
+        }
+    }
+
+    fn mac(&self, player_id: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("hmac accepts any key length");
+        mac.update(player_id.as_bytes());
+        mac
+    }
+
+    pub fn issue(&self, player_id: &str) -> String {
+        let signature = self.mac(player_id).finalize().into_bytes();
+        format!("{player_id}.{}", to_hex(&signature))
+    }
+
+    pub fn verify(&self, token: &str) -> Option<PlayerId> {
+        let (player_id, signature) = token.rsplit_once('.')?;
+        if player_id.is_empty() {
+            return None;
+        }
+        let signature = from_hex(signature)?;
+        self.mac(player_id).verify_slice(&signature).ok()?;
+        Some(PlayerId(format!("{TOKEN_PREFIX}{player_id}")))
+    }
+}
+
+impl Authenticator for HmacAuthenticator {
+    fn authenticate(&self, auth: &Auth) -> Option<PlayerId> {
+        match &auth.token {
+            Some(token) => self.verify(token),
+            None if self.allow_anonymous => Some(PlayerId::anonymous(&auth.player_name)),
+            None => None,
+        }
+    }
+}
+
+/// Scores saved before player ids are keyed by the bare display name.  Moves them to the
+/// player's anonymous id, merging names that only differed in case or spacing.  Names can look
+/// like ids, so only `Scores::version` tells whether this already happened.
+pub fn migrate_legacy_ids(scores: &mut Scores) {
+    if scores.version >= Scores::VERSION {
+        return;
+    }
+    for personal in [&mut scores.personal_blue, &mut scores.personal_pink] {
+        for (name, goals) in std::mem::take(personal) {
+            *personal.entry(PlayerId::anonymous(&name).0).or_insert(0) += goals;
+        }
+    }
+    for (name, stats) in std::mem::take(&mut scores.stats) {
+        let merged = scores.stats.entry(PlayerId::anonymous(&name).0);
+        merged.or_default().merge(&stats);
+    }
+    for (name, display) in std::mem::take(&mut scores.names) {
+        scores
+            .names
+            .entry(PlayerId::anonymous(&name).0)
+            .or_insert(display);
+    }
+    scores.version = Scores::VERSION;
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,129 @@
+//! Server-side lag compensation.
+//!
+//! Every server tick we record where every goalie was.  `KeyCommand`s carry the
+//! server tick the client was rendering when it kicked, so a late kick can be checked against
+//! the goalie the player actually saw instead of the one the server has now.  bevy_rapier can't
+//! rewind a simulation, so the shot isn't re-simulated; if the goalie at the seen tick would
+//! NOT have blocked the kick but the current goalie does, the ball is allowed to fly through the
+//! goalie for the rest of that shot.  Clients can claim any seen tick, so `rewind_tick` only
+//! trusts ticks as old as the user's measured round trip allows.
+use core::{
+    components::{Arena, Ball},
+    constants::*,
+    field::ball_collision_groups,
+};
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use naia_bevy_shared::Tick;
+use protocol::TICK_INTERVAL;
+
+/// Amount of past ticks to keep.  At the 40ms protocol tick this is ~1.3 seconds of history which
+/// is more than enough for the worst phones we've seen on venue Wi-Fi.
+pub const HISTORY_TICKS: usize = 32;
+
+/// Ticks on top of the round trip a kick may be rewound, for the client's interpolation delay
+/// (100ms by default) and jitter
+pub const REWIND_SLACK_TICKS: u16 = 4;
+
+const GRAVITY: f32 = 9.81;
+
+pub struct TickSnapshot {
+    pub tick: Tick,
+    /// One goalie per room
+    pub goalies: HashMap<Arena, Transform>,
+}
+
+/// Ring buffer of the last `HISTORY_TICKS` ticks of world state
+#[derive(Resource, Default)]
+pub struct WorldHistory {
+    snapshots: VecDeque<TickSnapshot>,
+}
+
+impl WorldHistory {
+    pub fn record(&mut self, snapshot: TickSnapshot) {
+        if self.snapshots.len() == HISTORY_TICKS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn at(&self, tick: Tick) -> Option<&TickSnapshot> {
+        self.snapshots.iter().rev().find(|s| s.tick == tick)
+    }
+}
+
+/// `seen_tick` if it is no further back from `now` than `rtt_ms` and `REWIND_SLACK_TICKS`
+/// explain.  Older ticks would let a modified client pick a moment the goalie was out of the way.
+pub fn rewind_tick(now: Tick, seen_tick: Tick, rtt_ms: f32) -> Option<Tick> {
+    let age = now.wrapping_sub(seen_tick) as i16;
+    let rtt_ticks = (rtt_ms.max(0.0) / TICK_INTERVAL.as_millis() as f32).ceil() as i16;
+    (age >= 0 && age <= rtt_ticks.saturating_add(REWIND_SLACK_TICKS as i16)).then_some(seen_tick)
+}
+
+/// Marks a ball whose current shot ignores the goalie because of lag compensation.
+#[derive(Component)]
+pub struct Compensated;
+
+/// `field::ball_collision_groups` without the goalie
+fn compensated_ball_collision_groups() -> CollisionGroups {
+    CollisionGroups::new(Group::GROUP_2, Group::GROUP_1)
+}
+
+/// Rough flight check of whether `goalie` stands between a ball kicked with `impulse` and the
+/// goal.  Damping and the magnus force are ignored, they don't move the ball far enough before it
+/// reaches the goalie to matter for this check.
+pub fn goalie_in_path(goalie: &Transform, ball: &Transform, impulse: Vec3) -> bool {
+    let velocity = impulse / BALL_MASS;
+    let dz = goalie.translation.z - ball.translation.z;
+    if velocity.z == 0.0 || dz.signum() != velocity.z.signum() {
+        return false;
+    }
+
+    let t = dz / velocity.z;
+    let x = ball.translation.x + velocity.x * t;
+    let y = ball.translation.y + velocity.y * t - 0.5 * GRAVITY * t * t;
+
+    // in the goalie's frame, so dives and jumps count
+    let local =
+        goalie.rotation.inverse() * (Vec3::new(x, y, goalie.translation.z) - goalie.translation);
+    let reach_x = GOALIE_RADIUS + BALL_RADIUS;
+    let reach_y = GOALIE_HEIGHT * 0.5 + GOALIE_RADIUS + BALL_RADIUS;
+    local.x.abs() < reach_x && local.y.abs() < reach_y
+}
+
+/// Called right after a ball has been kicked.  Returns true if the shot was compensated.
+pub fn compensate_kick(
+    commands: &mut Commands,
+    entity: Entity,
+    seen_goalie: &Transform,
+    current_goalie: &Transform,
+    ball_transform: &Transform,
+    impulse: Vec3,
+    collision_groups: &mut CollisionGroups,
+) -> bool {
+    let seen_blocked = goalie_in_path(seen_goalie, ball_transform, impulse);
+    let now_blocked = goalie_in_path(current_goalie, ball_transform, impulse);
+    if seen_blocked || !now_blocked {
+        return false;
+    }
+
+    *collision_groups = compensated_ball_collision_groups();
+    commands.entity(entity).insert(Compensated);
+    true
+}
+
+/// Give the goalie back to compensated balls once their shot is over
+pub fn restore_compensated(
+    mut commands: Commands,
+    mut ball_query: Query<(Entity, &Ball, &mut CollisionGroups), With<Compensated>>,
+) {
+    for (entity, ball, mut collision_groups) in ball_query.iter_mut() {
+        if !ball.shot {
+            *collision_groups = ball_collision_groups();
+            commands.entity(entity).remove::<Compensated>();
+        }
+    }
+}
@@ -1,9 +1,34 @@
 use core::{
-    components::{Ball, GoalieBehavior},
-    constants,
+    arena::ArenaHooks,
+    components::{Arena, Ball, GoalieBehavior, GoalieControl},
+    constants, field,
+    goalie_ai::GoalieAction,
+    rules::{KickModel, RulesPlugin, RulesSet, ShotOutcome, ShotOutcomeKind},
     systems::{goalie, magnus_effect},
 };
 
+pub mod admin;
+pub mod auth;
+pub mod lag_compensation;
+pub mod names;
+pub mod registry;
+pub mod rooms;
+pub mod scope;
+pub mod sessions;
+pub mod settings;
+pub mod store;
+pub mod version;
+
+use admin::{AdminConsole, Paused};
+use auth::{Anonymous, Authentication, HmacAuthenticator, PlayerId};
+use lag_compensation::{TickSnapshot, WorldHistory};
+use names::NamePolicy;
+use registry::{Pending, PlayerRegistry};
+use rooms::{Room, RoomManager};
+use sessions::Sessions;
+use settings::ServerSettings;
+use store::ScoreStorage;
+
 /// #NOTES
 /// Client controlled entity is handled like so:
 /// - Protocol needs to enable "client_authoritative_entities"
@@ -17,14 +42,25 @@ use core::{
 use protocol::{
     self,
     channels::{EntityAssignmentChannel, GameStateChannel, PlayerCommandChannel},
-    components::{EntityKind, Player, RepPhysics, UpdateWith},
-    messages::{Auth, EntityAssignment, KeyCommand, PlayerEvent, TotalScoreState},
-    primitives::{PlayColor, Scores},
+    components::{EntityKind, GoalieActionValue, GoalieState, Player, RepPhysics, UpdateWith},
+    messages::{
+        Announcement, Auth, EntityAssignment, GoalieCommand, KeyCommand, PlayerEvent,
+        PlayerStatsState, Rejected, ServerInfo, Session, TotalScoreState,
+    },
+    primitives::{PlayColor, PlayerStats, RejectReason, Scores},
 };
 
-use std::{collections::HashMap, fs, time::Duration};
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use bevy::app::{ScheduleRunnerPlugin, ScheduleRunnerSettings};
+use anyhow::Context;
+use bevy::app::{AppExit, ScheduleRunnerPlugin, ScheduleRunnerSettings};
 use bevy::asset::AssetPlugin;
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
@@ -40,125 +76,361 @@ use naia_bevy_server::{
         UpdateComponentEvents,
     },
     transport::webrtc,
-    CommandsExt, Plugin as ServerPlugin, ReceiveEvents, RoomKey, Server, ServerConfig, UserKey,
+    CommandsExt, Plugin as ServerPlugin, ReceiveEvents, Server, ServerConfig, UserKey,
 };
 use naia_bevy_shared::BeforeReceiveEvents;
 
 #[derive(Resource)]
 pub struct Global {
-    pub goalie_entity: Entity,
-    pub point_entity: Entity,
-
-    pub main_room_key: RoomKey,
-    pub player_to_entity: HashMap<UserKey, Entity>,
-    pub accept_queue: HashMap<UserKey, Player>,
-    pub entity_to_player: HashMap<Entity, UserKey>,
     pub scores: Scores,
+
+    /// Rejected users and the seconds left until they are disconnected
+    pub rejected: Vec<(UserKey, f32)>,
+    pub banned: HashSet<PlayerId>,
 }
 
+/// Time for the `Rejected` message to arrive before the user is disconnected
+pub(crate) const REJECT_DISCONNECT_DELAY: f32 = 1.0;
+
 pub fn auth_events(
-    mut global: ResMut<Global>,
+    global: Res<Global>,
+    mut registry: ResMut<PlayerRegistry>,
     mut server: Server,
+    settings: Res<ServerSettings>,
+    authentication: Res<Authentication>,
+    name_policy: Res<NamePolicy>,
+    sessions: Res<Sessions>,
     mut event_reader: EventReader<AuthEvents>,
+    player_query: Query<(&Player, &PlayerId)>,
 ) {
     for events in event_reader.iter() {
-        for (user_key, auth) in events.read::<Auth>() {
-            if auth.magic_number != protocol::MAGIC_NUMBER {
-                server.reject_connection(&user_key);
-            }
+        for (user_key, mut auth) in events.read::<Auth>() {
             server.accept_connection(&user_key);
+            // Falls through to a normal join when the session expired
+            if let Some(token) = &auth.resume {
+                if let Some(session) = sessions.held(token) {
+                    let pending = if !version::is_compatible(&auth.version) {
+                        Pending::Rejected(RejectReason::VersionMismatch)
+                    } else if global.banned.contains(&session.player_id) {
+                        Pending::Rejected(RejectReason::Banned)
+                    } else {
+                        Pending::Resume {
+                            token: token.clone(),
+                        }
+                    };
+                    registry.admit(user_key, pending);
+                    continue;
+                }
+            }
+            if auth.spectator {
+                let room = rooms::room_code(auth.room.as_deref());
+                let pending = match admit_watcher(&global, &authentication, &auth) {
+                    Ok(_) => Pending::Spectator { room },
+                    Err(reason) => Pending::Rejected(reason),
+                };
+                registry.admit(user_key, pending);
+                continue;
+            }
+            if auth.goalie {
+                let room = rooms::room_code(auth.room.as_deref());
+                // the goalie plays, so it takes a slot like a player
+                let pending = match admit_watcher(&global, &authentication, &auth) {
+                    Ok(_) if registry.slots_taken() >= settings.max_players => {
+                        Pending::Rejected(RejectReason::ServerFull)
+                    }
+                    Ok(_) => Pending::Goalie { room },
+                    Err(reason) => Pending::Rejected(reason),
+                };
+                registry.admit(user_key, pending);
+                continue;
+            }
 
-            global
-                .accept_queue
-                .insert(user_key, Player::new(auth.player_name, auth.player_color));
+            let admitted = admit(
+                &global,
+                &registry,
+                &settings,
+                &authentication,
+                &name_policy,
+                &player_query,
+                &mut auth,
+            );
+            match admitted {
+                Ok(player_id) => {
+                    let room = rooms::room_code(auth.room.as_deref());
+                    registry.admit(
+                        user_key,
+                        Pending::Player {
+                            player: Player::new(auth.player_name, auth.player_color),
+                            player_id,
+                            room,
+                        },
+                    );
+                }
+                Err(reason) => {
+                    info!(
+                        "rejecting {} ({}): {:?}",
+                        auth.player_name, auth.version, reason
+                    );
+                    registry.admit(user_key, Pending::Rejected(reason));
+                }
+            }
         }
     }
 }
 
+/// Like `admit` without the name checks, watchers don't get a ball or a name on the board
+fn admit_watcher(
+    global: &Global,
+    authentication: &Authentication,
+    auth: &Auth,
+) -> Result<PlayerId, RejectReason> {
+    if !version::is_compatible(&auth.version) {
+        return Err(RejectReason::VersionMismatch);
+    }
+    let player_id = authentication
+        .0
+        .authenticate(auth)
+        .ok_or(RejectReason::Unauthorized)?;
+    if global.banned.contains(&player_id) {
+        return Err(RejectReason::Banned);
+    }
+    Ok(player_id)
+}
+
+/// Normalizes `auth.player_name`, authenticates it and renames it if it is taken.  Anonymous ids
+/// come from the name before the rename, so "Jason 2" keeps Jason's scores.
+fn admit(
+    global: &Global,
+    registry: &PlayerRegistry,
+    settings: &ServerSettings,
+    authentication: &Authentication,
+    name_policy: &NamePolicy,
+    player_query: &Query<(&Player, &PlayerId)>,
+    auth: &mut Auth,
+) -> Result<PlayerId, RejectReason> {
+    if !version::is_compatible(&auth.version) {
+        return Err(RejectReason::VersionMismatch);
+    }
+
+    auth.player_name = name_policy.validate(&auth.player_name)?;
+    let player_id = authentication
+        .0
+        .authenticate(auth)
+        .ok_or(RejectReason::Unauthorized)?;
+    if global.banned.contains(&player_id) {
+        return Err(RejectReason::Banned);
+    }
+
+    let playing: Vec<(&str, &PlayerId)> = player_query
+        .iter()
+        .map(|(player, id)| (player.name.as_str(), id))
+        .chain(
+            registry
+                .pending_players()
+                .map(|(player, id)| (player.name.as_str(), id)),
+        )
+        .collect();
+    // anonymous players with the same name are told apart by `resolve_collision` instead
+    if !player_id.is_anonymous() && playing.iter().any(|(_, id)| **id == player_id) {
+        return Err(RejectReason::NameTaken);
+    }
+    let name = std::mem::take(&mut auth.player_name);
+    auth.player_name = name_policy.resolve_collision(name, |folded| {
+        playing.iter().any(|(name, _)| names::fold(name) == folded)
+    })?;
+
+    if registry.slots_taken() >= settings.max_players {
+        return Err(RejectReason::ServerFull);
+    }
+
+    Ok(player_id)
+}
+
+pub fn disconnect_rejected(time: Res<Time>, mut global: ResMut<Global>, mut server: Server) {
+    let dt = time.delta_seconds();
+    global.rejected.retain_mut(|(user_key, seconds_left)| {
+        *seconds_left -= dt;
+        if *seconds_left > 0.0 {
+            return true;
+        }
+        if server.user_exists(user_key) {
+            server.user_mut(user_key).disconnect();
+        }
+        false
+    });
+}
+
 pub fn connect_events(
     mut commands: Commands,
     mut server: Server,
     mut global: ResMut<Global>,
+    mut registry: ResMut<PlayerRegistry>,
+    mut rooms: ResMut<RoomManager>,
+    mut score_flush: ResMut<ScoreFlush>,
+    paused: Res<Paused>,
+    mut sessions: ResMut<Sessions>,
     mut event_reader: EventReader<ConnectEvent>,
 ) {
     for ConnectEvent(user_key) in event_reader.iter() {
-        let address = server
-            .user_mut(user_key)
-            // Add User to the main Room
-            .enter_room(&global.main_room_key)
-            // Get User's address for logging
-            .address();
-
-        info!("Naia Server connected to Client: {}", address);
-
-        let player_component = global
-            .accept_queue
-            .remove(user_key)
-            .expect("component exists ffrom auth_events. qed");
-
-        let ball_transform =
-            TransformBundle::from_transform(Transform::from_translation(constants::BALL_START));
-        let ball_velocity = Velocity::zero();
-        let ball_rep_physics = RepPhysics::new_with(&ball_transform.local, &ball_velocity);
+        let info = ServerInfo {
+            version: protocol::PROTOCOL_VERSION,
+            features: version::FEATURES.to_vec(),
+        };
+        server.send_message::<GameStateChannel, ServerInfo>(user_key, &info);
+        if let Some(notice) = paused.notice() {
+            server.send_message::<GameStateChannel, Announcement>(user_key, &notice);
+        }
+
+        let (player_component, player_id, room_code) = match registry.take_pending(user_key) {
+            Some(Pending::Player {
+                player,
+                player_id,
+                room,
+            }) => (player, player_id, room),
+            Some(Pending::Rejected(reason)) => {
+                reject(&mut server, &mut global, user_key, reason);
+                continue;
+            }
+            Some(Pending::Spectator { room: room_code }) => {
+                // only players create rooms, they come with a goal and a goalie
+                if !rooms.exists(&room_code) {
+                    reject(&mut server, &mut global, user_key, RejectReason::NoSuchRoom);
+                    continue;
+                }
+                let room = rooms.join(&mut commands, &mut server, &room_code, *user_key);
+                let total_message = TotalScoreState {
+                    blue: room.blue_total,
+                    pink: room.pink_total,
+                };
+                server.send_message::<GameStateChannel, TotalScoreState>(user_key, &total_message);
+                registry.connect_spectator(*user_key);
+                info!("spectator joined room {}", room_code);
+                continue;
+            }
+            Some(Pending::Goalie { room: room_code }) => {
+                // taken before joining, a rejected user mustn't stay in the room
+                let rejection = match rooms.get_mut(&room_code) {
+                    None => Some(RejectReason::NoSuchRoom),
+                    Some(room) if !room.take_goalie(&mut commands, *user_key) => {
+                        Some(RejectReason::GoalieTaken)
+                    }
+                    Some(_) => None,
+                };
+                if let Some(reason) = rejection {
+                    reject(&mut server, &mut global, user_key, reason);
+                    continue;
+                }
+                let room = rooms.join(&mut commands, &mut server, &room_code, *user_key);
+                let total_message = TotalScoreState {
+                    blue: room.blue_total,
+                    pink: room.pink_total,
+                };
+                server.send_message::<GameStateChannel, TotalScoreState>(user_key, &total_message);
+                registry.connect_goalie(*user_key);
+                info!("a user took over the goalie of room {}", room_code);
+                continue;
+            }
+            Some(Pending::Resume { token }) => {
+                let Some(dropped) = sessions.resume(&token, *user_key) else {
+                    // the client tries again and joins normally
+                    info!("session expired while reconnecting");
+                    server.user_mut(user_key).disconnect();
+                    continue;
+                };
+                // Join before leaving so the room isn't destroyed in between
+                let room_code = rooms
+                    .of_user(&dropped.user_key)
+                    .map_or(rooms::MAIN_ROOM.to_owned(), |room| room.code.clone());
+                let room = rooms.join(&mut commands, &mut server, &room_code, *user_key);
+                let total_message = TotalScoreState {
+                    blue: room.blue_total,
+                    pink: room.pink_total,
+                };
+                rooms.leave(&mut commands, &mut server, &dropped.user_key);
+                info!("{} resumed in room {}", dropped.player_id.0, room_code);
+
+                let stats = global.scores.stats_mut(&dropped.player_id.0);
+                stats.seen(unix_now());
+                send_stats(&mut server, user_key, stats);
+                registry.connect_player(*user_key, dropped.ball);
+                assign_ball(&mut server, user_key, &dropped.ball, token);
+                server.send_message::<GameStateChannel, TotalScoreState>(user_key, &total_message);
+                continue;
+            }
+            // timed out in `expire_pending`
+            None => {
+                info!("user connected after their admission expired");
+                server.user_mut(user_key).disconnect();
+                continue;
+            }
+        };
+
+        let room = rooms.join(&mut commands, &mut server, &room_code, *user_key);
+        let (room_key, arena) = (room.key, room.arena);
+        let total_message = TotalScoreState {
+            blue: room.blue_total,
+            pink: room.pink_total,
+        };
+
+        let address = server.user(user_key).address();
+        info!(
+            "Naia Server connected to Client: {} in room {}",
+            address, room_code
+        );
+
+        global
+            .scores
+            .names
+            .insert(player_id.0.clone(), (*player_component.name).clone());
+        let stats = global.scores.stats_mut(&player_id.0);
+        stats.seen(unix_now());
+        send_stats(&mut server, user_key, stats);
+        score_flush.dirty = true;
+
+        let ball_transform = Transform::from_translation(constants::BALL_START);
+        let ball_rep_physics = RepPhysics::new_with(&ball_transform, &Velocity::zero());
         let ball_entity = commands
-            .spawn((
+            .spawn(field::ball_body(arena))
+            .insert((
                 EntityKind::ball(),
                 player_component,
-                Ball::default(),
-                // Position::from(constants::BALL_START),
+                player_id.clone(),
                 ball_rep_physics,
             ))
-            .insert((
-                TransformBundle::from_transform(Transform::from_translation(constants::BALL_START)),
-                RigidBody::Dynamic,
-                // Group2 is the ball group.  Group1 is the goal/goalie group
-                CollisionGroups::new(Group::GROUP_2, Group::GROUP_1),
-                Collider::ball(constants::BALL_RADIUS),
-                ColliderMassProperties::Mass(constants::BALL_MASS),
-                ball_velocity,
-                Friction::new(5.0),
-                ExternalForce::default(),
-                ExternalImpulse::default(),
-                GravityScale::default(),
-                Damping {
-                    linear_damping: 1.0,
-                    angular_damping: 2.0,
-                },
-                Restitution {
-                    coefficient: 1.0,
-                    combine_rule: CoefficientCombineRule::Average,
-                },
-                Sleeping::default(),
-            ))
             .enable_replication(&mut server)
             .id();
 
-        server
-            .room_mut(&global.main_room_key)
-            .add_entity(&ball_entity);
-        global.player_to_entity.insert(*user_key, ball_entity);
-        global.entity_to_player.insert(ball_entity, *user_key);
-
-        // Send an Entity Assignment message to the User that owns the Square
-        let mut assignment_message = EntityAssignment::new(true);
-        assignment_message.entity.set(&server, &ball_entity);
-        server.send_message::<EntityAssignmentChannel, EntityAssignment>(
-            user_key,
-            &assignment_message,
-        );
+        server.room_mut(&room_key).add_entity(&ball_entity);
+        registry.connect_player(*user_key, ball_entity);
+
+        let token = sessions.start(*user_key, ball_entity, player_id);
+        assign_ball(&mut server, user_key, &ball_entity, token);
 
         // Send Score Snapshots
-        let total_message = TotalScoreState {
-            blue: global.scores.blue_total,
-            pink: global.scores.pink_total,
-        };
         server.send_message::<GameStateChannel, TotalScoreState>(user_key, &total_message);
     }
 }
 
-// Destroy User's entities
+/// Tells the user why and disconnects them once the message had time to arrive
+fn reject(server: &mut Server, global: &mut Global, user_key: &UserKey, reason: RejectReason) {
+    server.send_message::<GameStateChannel, Rejected>(user_key, &Rejected { reason });
+    global.rejected.push((*user_key, REJECT_DISCONNECT_DELAY));
+}
+
+/// Sends the user their ball along with the `Session` token to get it back after a drop
+fn assign_ball(server: &mut Server, user_key: &UserKey, ball: &Entity, token: String) {
+    let mut assignment_message = EntityAssignment::new(true);
+    assignment_message.entity.set(server, ball);
+    server.send_message::<EntityAssignmentChannel, EntityAssignment>(user_key, &assignment_message);
+    server.send_message::<GameStateChannel, Session>(user_key, &Session { token });
+}
+
+/// Destroys the user's ball, unless their session is held for them to reconnect
 pub fn disconnect_events(
-    mut global: ResMut<Global>,
+    settings: Res<ServerSettings>,
+    mut sessions: ResMut<Sessions>,
+    mut registry: ResMut<PlayerRegistry>,
+    mut rooms: ResMut<RoomManager>,
     mut server: Server,
     mut commands: Commands,
     mut event_reader: EventReader<DisconnectEvent>,
@@ -166,12 +438,53 @@ pub fn disconnect_events(
     for DisconnectEvent(user_key, user) in event_reader.iter() {
         info!("Naia Server disconnected from: {:?}", user.address);
 
-        if let Some(entity) = global.player_to_entity.remove(user_key) {
+        let has_ball = registry.ball_of(user_key).is_some();
+        if has_ball && !settings.resume_grace.is_zero() && sessions.hold(user_key) {
+            // keeps the owner so a shot still in flight is credited
+            registry.hold(*user_key);
+            info!("holding the ball for {:?}", settings.resume_grace);
+            continue;
+        }
+        if let Some(entity) = registry.disconnect(user_key) {
+            sessions.end(user_key);
             commands.entity(entity).despawn();
-            server
-                .room_mut(&global.main_room_key)
-                .remove_entity(&entity);
+            if let Some(room) = rooms.of_user(user_key) {
+                server.room_mut(&room.key).remove_entity(&entity);
+            }
         }
+        rooms.leave(&mut commands, &mut server, user_key);
+    }
+}
+
+/// Forgets users that were admitted but never connected
+pub fn expire_pending(time: Res<Time>, mut registry: ResMut<PlayerRegistry>, mut server: Server) {
+    for user_key in registry.expire_pending(time.delta_seconds()) {
+        info!("admitted user never connected");
+        if server.user_exists(&user_key) {
+            server.user_mut(&user_key).disconnect();
+        }
+    }
+}
+
+/// Despawns the balls of users that didn't come back in time
+pub fn expire_sessions(
+    time: Res<Time>,
+    settings: Res<ServerSettings>,
+    mut sessions: ResMut<Sessions>,
+    mut registry: ResMut<PlayerRegistry>,
+    mut rooms: ResMut<RoomManager>,
+    mut server: Server,
+    mut commands: Commands,
+) {
+    let grace = settings.resume_grace.as_secs_f32();
+    for session in sessions.expire(time.delta_seconds(), grace) {
+        info!("session of {} expired", session.player_id.0);
+        registry.disconnect(&session.user_key);
+        commands.entity(session.ball).despawn();
+        if let Some(room) = rooms.of_user(&session.user_key) {
+            server.room_mut(&room.key).remove_entity(&session.ball);
+        }
+        rooms.leave(&mut commands, &mut server, &session.user_key);
     }
 }
 
@@ -182,49 +495,120 @@ pub fn error_events(mut event_reader: EventReader<ErrorEvent>) {
 }
 
 pub fn tick_events(
+    time: Res<Time>,
+    mut commands: Commands,
     mut server: Server,
-    mut ball_query: Query<(&mut Transform, &mut Ball, &mut ExternalImpulse)>,
+    mut global: ResMut<Global>,
+    rooms: Res<RoomManager>,
+    mut history: ResMut<WorldHistory>,
+    mut score_flush: ResMut<ScoreFlush>,
+    kick_model: Res<KickModel>,
+    paused: Res<Paused>,
+    mut ball_query: Query<(
+        &Transform,
+        &mut Ball,
+        &mut ExternalImpulse,
+        &mut CollisionGroups,
+        &PlayerId,
+        &Arena,
+    )>,
+    goalie_query: Query<(&Transform, &Arena), (With<GoalieBehavior>, Without<Ball>)>,
+    mut control_query: Query<&mut GoalieControl>,
     mut tick_reader: EventReader<TickEvent>,
 ) {
-    let mut has_ticked = false;
-
     for TickEvent(server_tick) in tick_reader.iter() {
-        has_ticked = true;
-
         // All game logic should happen here, on a tick event
 
+        history.record(TickSnapshot {
+            tick: *server_tick,
+            goalies: goalie_query
+                .iter()
+                .map(|(transform, arena)| (*arena, *transform))
+                .collect(),
+        });
+
         let mut messages = server.receive_tick_buffer_messages(server_tick);
-        for (_user_key, key_command) in messages.read::<PlayerCommandChannel, KeyCommand>() {
+        for (user_key, key_command) in messages.read::<PlayerCommandChannel, KeyCommand>() {
+            if paused.0 {
+                continue;
+            }
             let Some(entity) = &key_command.entity.get(&server) else {
                 continue;
             };
 
-            if let Ok((mut transform, mut ball, mut ext_i)) = ball_query.get_mut(*entity) {
+            if let Ok((transform, mut ball, mut ext_i, mut collision_groups, player_id, arena)) =
+                ball_query.get_mut(*entity)
+            {
+                let Some(room) = rooms.of_arena(*arena) else {
+                    continue;
+                };
+                let seen_tick = key_command.seen_tick;
+                let was_shot = ball.shot;
                 // let ray_normal = Vec3::new(0.015694855, -0.011672409, 0.9998087);
                 // let ray_point = Vec3::new(0.0017264052, 0.0070980787, 42.109978);
-                process_ball_command(
+                let kicked = process_ball_command(
                     &mut server,
+                    room,
+                    &kick_model,
                     entity,
                     key_command,
-                    &mut transform,
+                    transform,
                     &mut ball,
                     &mut ext_i,
                 );
+                if kicked {
+                    commands
+                        .entity(*entity)
+                        .insert(scope::LastKick(time.elapsed_seconds_f64()));
+                    let stats = global.scores.stats_mut(&player_id.0);
+                    stats.kicked(unix_now());
+                    send_stats(&mut server, &user_key, stats);
+                    score_flush.dirty = true;
+                }
+
+                // Resolve the kick against the goalie the client saw when it kicked
+                let rtt = server.rtt(&user_key).unwrap_or_default();
+                let seen = seen_tick
+                    .and_then(|tick| lag_compensation::rewind_tick(*server_tick, tick, rtt))
+                    .and_then(|tick| history.at(tick));
+                let Ok((goalie_transform, ..)) = goalie_query.get(room.goalie_entity) else {
+                    continue;
+                };
+                let seen_goalie = seen.and_then(|seen| Some((seen.tick, seen.goalies.get(arena)?)));
+                if let (false, true, Some((seen_tick, seen_goalie))) =
+                    (was_shot, ball.shot, seen_goalie)
+                {
+                    if lag_compensation::compensate_kick(
+                        &mut commands,
+                        *entity,
+                        seen_goalie,
+                        goalie_transform,
+                        transform,
+                        ext_i.impulse,
+                        &mut collision_groups,
+                    ) {
+                        info!("lag compensated kick from tick {}", seen_tick);
+                    }
+                }
             }
         }
-    }
-
-    if has_ticked {
-        // Update scopes of entities
-        for (_, user_key, entity) in server.scope_checks() {
-            // You'd normally do whatever checks you need to in here..
-            // to determine whether each Entity should be in scope or not.
-
-            // This indicates the Entity should be in this scope.
-            server.user_scope(&user_key).include(&entity);
 
-            // And call this if Entity should NOT be in this scope.
-            // server.user_scope(..).exclude(..);
+        for (user_key, goalie_command) in messages.read::<PlayerCommandChannel, GoalieCommand>() {
+            if paused.0 {
+                continue;
+            }
+            let Some(room) = rooms
+                .of_user(&user_key)
+                .filter(|room| room.keeper == Some(user_key))
+            else {
+                continue;
+            };
+            if let Ok(mut control) = control_query.get_mut(room.goalie_entity) {
+                control.direction = goalie_command.direction.clamp(-1, 1) as f32;
+                if let Some(action) = goalie_command.action {
+                    control.action = Some(goalie_action(action));
+                }
+            }
         }
     }
 }
@@ -275,142 +659,202 @@ pub fn remove_component_events(mut event_reader: EventReader<RemoveComponentEven
     }
 }
 
+/// Returns true if the ball was kicked
 pub fn process_ball_command(
     server: &mut Server,
+    room: &Room,
+    kick_model: &KickModel,
     entity: &Entity,
     key_command: KeyCommand,
-    transform: &mut Transform,
+    transform: &Transform,
     ball: &mut Ball,
     ext_i: &mut ExternalImpulse,
-) {
+) -> bool {
     if key_command.reset && ball.shot && !ball.scored {
         ball.force_reset = true;
-        return;
+        return false;
     }
 
-    //#HACK we stop shots from happening unless the ball is settled on the floor
-    //this works for now minus the spamming inbetween the bounce of the ball when spawned.
-    //probably should set this to a cooldown.  Maybe the cooldown can be implemented in the client
-    //side only for now
     let Some((ray_normal, ray_point)) = key_command.shoot else {
-        return;
+        return false;
     };
 
-    if !ball.shot && transform.translation.y < 0.01 {
-        let ray_normal = Vec3::new(ray_normal.x, ray_normal.y - 0.8, ray_normal.z);
-        let ray_point = Vec3::new(ray_point.x, ray_point.y, ray_point.z);
-        let kick_force = Vec3::new(-2.0, -3.0, -13.0);
-        let impulse = ray_normal * kick_force;
-        // let impulse_camera = camera_rotation.normalize() * force.neg();
-        *ext_i = ExternalImpulse::at_point(impulse, ray_point, transform.translation);
-        ext_i.torque_impulse = ext_i.torque_impulse * 0.15;
-        // ext_i.impulse = impulse_camera;
-        ball.shot = true;
-
-        let mut message = PlayerEvent::kicked();
-        message.entity.set(server, entity);
-        server.broadcast_message::<GameStateChannel, PlayerEvent>(&message);
+    if !kick_model.kick(ray_normal.into(), ray_point.into(), transform, ball, ext_i) {
+        return false;
     }
+
+    let mut message = PlayerEvent::kicked();
+    message.entity.set(server, entity);
+    room.broadcast::<GameStateChannel, PlayerEvent>(server, &message);
+    true
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn send_stats(server: &mut Server, user_key: &UserKey, stats: &PlayerStats) {
+    let message = PlayerStatsState {
+        stats: stats.clone(),
+    };
+    server.send_message::<GameStateChannel, PlayerStatsState>(user_key, &message);
 }
 
+/// Goals count towards the room's tally and, in the main room, the persisted team totals.
+/// Personal scores and stats are kept whichever room the player is in.
 pub fn ball_score(
     mut global: ResMut<Global>,
+    registry: Res<PlayerRegistry>,
+    mut rooms: ResMut<RoomManager>,
     mut server: Server,
-    mut collision_events: EventReader<CollisionEvent>,
-    mut ball_query: Query<(&mut Ball, &Player)>,
-    // mut goalie_query: Query<&mut GoalieBehavior>,
+    settings: Res<ServerSettings>,
+    storage: Res<ScoreStorage>,
+    mut score_flush: ResMut<ScoreFlush>,
+    mut outcome_events: EventReader<ShotOutcome>,
+    player_query: Query<(&Player, &PlayerId, &Arena)>,
 ) {
-    for event in collision_events.iter() {
-        // log::info!("Received collision event: {:?}", event);
-        if let CollisionEvent::Started(entity, entity2, _) = event {
-            if entity == &global.point_entity {
-                if let Ok((mut ball, player)) = ball_query.get_mut(*entity2) {
-                    ball.scored = true;
-                    let player_color = *player.color;
-                    let mut message = if let PlayColor::Pink = player_color {
+    for outcome in outcome_events.iter() {
+        let Ok((player, player_id, arena)) = player_query.get(outcome.ball) else {
+            continue;
+        };
+        let Some(room) = rooms.of_arena_mut(*arena) else {
+            continue;
+        };
+        let main_room = room.code == rooms::MAIN_ROOM;
+        let mut message = match outcome.kind {
+            ShotOutcomeKind::Goal => {
+                let message = if let PlayColor::Pink = *player.color {
+                    room.pink_total += 1;
+                    if main_room {
                         global.scores.pink_total += 1;
-                        *global
-                            .scores
-                            .personal_pink
-                            .entry((*player.name).clone())
-                            .or_insert(0) += 1;
-                        PlayerEvent::pink_scored()
-                    } else {
-                        *global
-                            .scores
-                            .personal_blue
-                            .entry((*player.name).clone())
-                            .or_insert(0) += 1;
+                    }
+                    *global
+                        .scores
+                        .personal_pink
+                        .entry(player_id.0.clone())
+                        .or_insert(0) += 1;
+                    PlayerEvent::pink_scored()
+                } else {
+                    *global
+                        .scores
+                        .personal_blue
+                        .entry(player_id.0.clone())
+                        .or_insert(0) += 1;
+                    room.blue_total += 1;
+                    if main_room {
                         global.scores.blue_total += 1;
-                        PlayerEvent::blue_scored()
-                    };
-
-                    message.entity.set(&server, &entity2);
-                    server.broadcast_message::<GameStateChannel, PlayerEvent>(&message);
-                }
-            } else if entity == &global.goalie_entity {
-                let mut deny_message = PlayerEvent::new_denied_goalie();
-                deny_message.entity.set(&server, &entity2);
-                server.broadcast_message::<GameStateChannel, PlayerEvent>(&deny_message);
-            } else {
-                //must've hit the goal frame.  Balls don't hit balls and goalies don't score points
-                //or hit the frame
-                let mut deny_message = PlayerEvent::new_denied_frame();
-                deny_message.entity.set(&server, &entity2);
-                server.broadcast_message::<GameStateChannel, PlayerEvent>(&deny_message);
+                    }
+                    PlayerEvent::blue_scored()
+                };
+                global.scores.stats_mut(&player_id.0).scored();
+                message
             }
+            ShotOutcomeKind::DeniedGoalie => {
+                global.scores.stats_mut(&player_id.0).denied_by_goalie();
+                PlayerEvent::new_denied_goalie()
+            }
+            ShotOutcomeKind::DeniedFrame => {
+                global.scores.stats_mut(&player_id.0).denied_by_frame();
+                PlayerEvent::new_denied_frame()
+            }
+        };
+        score_flush.dirty = true;
+        // held players get their stats when they resume
+        let owner = registry.owner_of(outcome.ball);
+        if let Some(user_key) = owner.filter(|user_key| !registry.is_held(user_key)) {
+            send_stats(
+                &mut server,
+                &user_key,
+                global.scores.stats_mut(&player_id.0),
+            );
         }
+
+        message.entity.set(&server, &outcome.ball);
+        room.broadcast::<GameStateChannel, PlayerEvent>(&mut server, &message);
     }
-}
 
-#[derive(Resource)]
-pub struct LastFlush(f32);
-// const STORAGE_PATH: &str = "/var/powerbaby.json";
-const STORAGE_PATH: &str = "./powerbaby.json";
-pub fn flush_scores(time: Res<Time>, global: Res<Global>, mut last_flush: ResMut<LastFlush>) {
-    last_flush.0 += time.delta_seconds();
-    if last_flush.0 >= 30.0 {
-        last_flush.0 = 0.0;
+    if settings.write_through && score_flush.dirty {
+        score_flush.save(&storage, &global.scores);
     }
+}
+
+/// Seconds between saves of changed scores when not in write-through mode
+const FLUSH_INTERVAL: f32 = 30.0;
+
+#[derive(Resource, Default)]
+pub struct ScoreFlush {
+    since_last: f32,
+    /// Scores changed since the last successful save
+    pub dirty: bool,
+}
 
-    let elapsed = time.elapsed_seconds() as u64;
-    if elapsed > 0 && (elapsed % 30 == 0) {
-        let score_bytes = serde_json::to_vec_pretty(&global.scores).unwrap();
-        fs::write(STORAGE_PATH, score_bytes).unwrap();
+impl ScoreFlush {
+    /// Failed saves stay dirty and are retried on the next flush
+    pub fn save(&mut self, storage: &ScoreStorage, scores: &Scores) {
+        self.since_last = 0.0;
+        match storage.0.save(scores) {
+            Ok(()) => self.dirty = false,
+            Err(err) => error!("failed to save scores: {err:#}"),
+        }
     }
 }
 
-pub fn ball_reset(
+pub fn flush_scores(
     time: Res<Time>,
-    mut ball_query: Query<(
-        &mut Transform,
-        &mut Ball,
-        &mut ExternalForce,
-        &mut ExternalImpulse,
-        &mut Velocity,
-    )>,
+    global: Res<Global>,
+    storage: Res<ScoreStorage>,
+    mut score_flush: ResMut<ScoreFlush>,
 ) {
-    for (mut transform, mut ball, mut ext_f, mut ext_i, mut velocity) in ball_query.iter_mut() {
-        if ball.shot {
-            ball.shot_elapsed += time.delta_seconds();
-        }
+    score_flush.since_last += time.delta_seconds();
+    if score_flush.dirty && score_flush.since_last >= FLUSH_INTERVAL {
+        score_flush.save(&storage, &global.scores);
+    }
+}
 
-        if ball.shot_elapsed >= constants::BALL_SHOT_WAIT_TIME
-            || ball.force_reset
-            || (ball.scored && ball.shot_elapsed >= 1.0)
-        {
-            *ext_f = ExternalForce::default();
-            *ext_i = ExternalImpulse::default();
-            *velocity = Velocity::zero();
-            *transform = Transform::from_translation(constants::BALL_START);
-            ball.shot = false;
-            ball.scored = false;
-            ball.force_reset = false;
-            ball.shot_elapsed = 0.0;
-        }
+/// Shared with whoever stops the server, e.g. the binary's SIGINT/SIGTERM handler
+#[derive(Resource, Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+impl Shutdown {
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
     }
 }
 
+/// Saves the scores and disconnects everyone, then exits on the following frame so naia gets a
+/// chance to send the disconnects.
+pub fn shutdown(
+    shutdown: Res<Shutdown>,
+    mut server: Server,
+    global: Res<Global>,
+    storage: Res<ScoreStorage>,
+    mut score_flush: ResMut<ScoreFlush>,
+    mut app_exit: EventWriter<AppExit>,
+    mut disconnected: Local<bool>,
+) {
+    if !shutdown.is_requested() {
+        return;
+    }
+    if *disconnected {
+        app_exit.send(AppExit);
+        return;
+    }
+
+    info!("shutting down, saving scores and disconnecting users");
+    score_flush.save(&storage, &global.scores);
+    for user_key in server.user_keys() {
+        server.user_mut(&user_key).disconnect();
+    }
+    *disconnected = true;
+}
+
 // in server after physics systems before naia 'ReceiveEvents' systems
 pub fn sync_physics(
     mut query: Query<(&Transform, &Velocity, &Sleeping, &mut RepPhysics), Changed<Transform>>,
@@ -422,12 +866,45 @@ pub fn sync_physics(
     }
 }
 
+/// Replicates the goalies' dives and jumps, see `core::goalie_ai::GoalieAction`
+pub fn sync_goalie_state(
+    mut query: Query<(&GoalieBehavior, &mut GoalieState), Changed<GoalieBehavior>>,
+) {
+    for (behavior, mut state) in query.iter_mut() {
+        let action = match behavior.action {
+            GoalieAction::Stand => GoalieActionValue::Stand,
+            GoalieAction::DiveLeft => GoalieActionValue::DiveLeft,
+            GoalieAction::DiveRight => GoalieActionValue::DiveRight,
+            GoalieAction::Jump => GoalieActionValue::Jump,
+        };
+        if *state.action != action {
+            *state.action = action;
+        }
+    }
+}
+
+fn goalie_action(value: GoalieActionValue) -> GoalieAction {
+    match value {
+        GoalieActionValue::Stand => GoalieAction::Stand,
+        GoalieActionValue::DiveLeft => GoalieAction::DiveLeft,
+        GoalieActionValue::DiveRight => GoalieAction::DiveRight,
+        GoalieActionValue::Jump => GoalieAction::Jump,
+    }
+}
+
 pub fn init(
     mut commands: Commands,
     mut server: Server,
+    settings: Res<ServerSettings>,
+    global: Res<Global>,
+    name_policy: Res<NamePolicy>,
     // mut rapier_config: ResMut<RapierConfiguration>,
 ) {
     info!("Naia Bevy Server Demo init");
+    info!(
+        "signaling on {}, data on {}, advertising {}",
+        settings.signal_addr, settings.data_addr, settings.public_url
+    );
 
     // rapier_config.timestep_mode = TimestepMode::Fixed {
     //     // dt: constants::TIME_STEP,
@@ -437,145 +914,87 @@ pub fn init(
 
     // Naia Server initialization
     let server_addresses = webrtc::ServerAddrs::new(
-        "0.0.0.0:14191"
-            .parse()
-            .expect("could not parse Signaling address/port"),
+        settings.signal_addr,
         // IP Address to listen on for UDP WebRTC data channels
-        "0.0.0.0:14192"
-            .parse()
-            .expect("could not parse WebRTC data address/port"),
+        settings.data_addr,
         // The public WebRTC IP address to advertise
-        protocol::SERVER_AD_URL,
+        &settings.public_url,
     );
     let socket = webrtc::Socket::new(&server_addresses, server.socket_config());
     server.listen(socket);
 
-    // Create a new, singular room, which will contain Users and Entities that they
-    // can receive updates from
-    let main_room_key = server.make_room().key();
-
-    let (goalie_entity, point_entity) = init_physics(&mut commands, &mut server, &main_room_key);
+    init_physics(&mut commands);
 
-    //load scores
-    let scores_json: String = fs::read_to_string(STORAGE_PATH).unwrap_or_default();
-    let scores: Scores = serde_json::from_str(&scores_json).unwrap_or_default();
     // Resources
-    commands.insert_resource(Global {
-        goalie_entity,
-        point_entity,
-        main_room_key,
-        player_to_entity: HashMap::new(),
-        entity_to_player: HashMap::new(),
-        accept_queue: HashMap::new(),
-        scores,
-    });
-    commands.insert_resource(LastFlush(0.0));
+    // The main room always exists, other rooms are created when players join them
+    let rooms = RoomManager::new(
+        &mut commands,
+        &mut server,
+        global.scores.blue_total,
+        global.scores.pink_total,
+        settings.goalie,
+    );
+    commands.insert_resource(rooms);
+    commands.insert_resource(PlayerRegistry::default());
+    if settings.name_blocklist.is_some() {
+        info!("{} blocked words", name_policy.blocklist.len());
+    }
+    commands.insert_resource(Authentication(match &settings.auth_secret {
+        Some(secret) => Box::new(HmacAuthenticator::new(
+            secret.as_bytes(),
+            !settings.require_token,
+        )),
+        None => Box::new(Anonymous),
+    }));
+    commands.insert_resource(ScoreFlush::default());
+    commands.insert_resource(WorldHistory::default());
+    commands.insert_resource(Sessions::default());
 }
 
-pub fn init_physics(
-    commands: &mut Commands,
-    server: &mut Server,
-    main_room_key: &RoomKey,
-) -> (Entity, Entity) {
+/// Rooms share the ground, each room spawns its own goal and goalie, see `rooms`
+pub fn init_physics(commands: &mut Commands) {
     log::info!("init_physics");
 
     //#NOTE this is not a replicated entity, the client must render this in the init function.  the
     //ground never changes.
-    commands.spawn((
-        Name::new("Ground"),
-        TransformBundle::from(Transform::from_xyz(0.0, constants::GROUND_HEIGHT, 0.0)),
-        Collider::cuboid(constants::GROUND_SIZE, 0.0, constants::GROUND_SIZE),
-        RigidBody::KinematicPositionBased,
-        Friction::new(100.0),
-    ));
-
-    // Create a goal rigid-body with multiple colliders attached, using Bevy hierarchy.
-    let x = 0.0;
-    let y = constants::GROUND_HEIGHT;
-    let z = 32.0;
-    let rad = 0.2;
-    let mut point_entity: Entity = Entity::PLACEHOLDER;
-    commands
-        .spawn((
-            Name::new("Goal"),
-            TransformBundle::from(Transform::from_xyz(x, y, z)),
-            RigidBody::KinematicPositionBased,
-            CollisionGroups::new(Group::GROUP_1, Group::GROUP_2),
-        ))
-        .with_children(|p| {
-            p.spawn((
-                Name::new("FrameTop"),
-                TransformBundle::from(Transform::from_xyz(0.0, rad * 10.0, 0.0)),
-                Collider::cuboid(rad * 10.0, rad * 0.5, rad),
-                ActiveEvents::COLLISION_EVENTS,
-            ));
-            p.spawn((
-                Name::new("FrameLeft"),
-                TransformBundle::from(Transform::from_xyz(rad * 10.0, rad * 5.0, 0.0)),
-                Collider::cuboid(rad * 0.5, rad * 5.0, rad),
-                ActiveEvents::COLLISION_EVENTS,
-            ));
-            p.spawn((
-                Name::new("FrameRight"),
-                TransformBundle::from(Transform::from_xyz(-rad * 10.0, rad * 5.0, 0.0)),
-                Collider::cuboid(rad * 0.5, rad * 5.0, rad),
-                ActiveEvents::COLLISION_EVENTS,
-            ));
-            point_entity = p
-                .spawn((
-                    Name::new("PointZone"),
-                    TransformBundle::from(Transform::from_xyz(0.0, rad * 5.0, (-rad * 0.5) - rad)),
-                    Sensor,
-                    Collider::cuboid(rad * 10.0, rad * 5.0, rad * 0.5),
-                    ActiveEvents::COLLISION_EVENTS,
-                ))
-                .id();
-        });
-
-    let goalie_transform =
-        TransformBundle::from_transform(Transform::from_translation(constants::GOALIE_START));
-    let goalie_velocity = Velocity::zero();
-    let goalie_rep_physics = RepPhysics::new_with(&goalie_transform.local, &goalie_velocity);
-    let goalie = commands
-        .spawn((
-            Name::new("Goalie"),
-            EntityKind::goalie(),
-            GoalieBehavior::default(),
-            goalie_rep_physics,
-            // Sensor,
-            TransformBundle::from_transform(Transform {
-                translation: constants::GOALIE_START,
-                // rotation: Quat::from_rotation_y(4.71239),
-                ..Default::default()
-            }),
-            RigidBody::KinematicPositionBased,
-            CollisionGroups::new(Group::GROUP_1, Group::GROUP_2),
-            Collider::capsule_y(constants::GOALIE_HEIGHT * 0.5, constants::GOALIE_RADIUS),
-            GravityScale::default(),
-            Damping {
-                linear_damping: 1.0,
-                angular_damping: 5.0,
-            },
-            Restitution {
-                coefficient: 1.0,
-                combine_rule: CoefficientCombineRule::Average,
-            },
-            goalie_velocity,
-            Sleeping::default(),
-            ActiveEvents::COLLISION_EVENTS,
-        ))
-        .enable_replication(server)
-        .id();
-
-    server.room_mut(main_room_key).add_entity(&goalie);
-
-    (goalie, point_entity)
+    field::spawn_ground(commands);
 }
 
-pub fn run() {
+/// Returns once `shutdown_flag` has been requested and the scores are saved.  Fails if the scores
+/// or the name blocklist can't be read, rather than starting with empty scores that would
+/// overwrite them.
+pub fn run(settings: ServerSettings, shutdown_flag: Shutdown) -> anyhow::Result<()> {
     info!("powerbaby server startup");
 
-    App::default()
+    let storage = store::open(&settings.store)
+        .with_context(|| format!("can't open score store {}", settings.store))?;
+    let mut scores = storage
+        .load()
+        .with_context(|| format!("can't load scores from {}", settings.store))?;
+    auth::migrate_legacy_ids(&mut scores);
+    let mut name_policy = NamePolicy {
+        collision: settings.name_collision,
+        ..default()
+    };
+    if let Some(path) = &settings.name_blocklist {
+        name_policy.blocklist =
+            names::load_blocklist(path).context("can't load the name blocklist")?;
+    }
+
+    let mut app = App::default();
+    if settings.admin_console {
+        println!("{}", admin::HELP);
+        app.insert_resource(AdminConsole::stdin());
+    }
+    app.insert_resource(settings)
+        .insert_resource(shutdown_flag)
+        .insert_resource(ScoreStorage(storage))
+        .insert_resource(name_policy)
+        .insert_resource(Global {
+            scores,
+            rejected: Vec::new(),
+            banned: HashSet::new(),
+        })
         .add_plugin(TaskPoolPlugin::default())
         .add_plugin(TypeRegistrationPlugin::default())
         .add_plugin(FrameCountPlugin::default())
@@ -592,9 +1011,9 @@ pub fn run() {
         .add_plugin(ScenePlugin::default())
         .add_plugin(TimePlugin::default())
         .add_asset::<Mesh>()
-        .add_asset::<Scene>()
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugin(RngPlugin::new().with_rng_seed(0772))
+        .add_asset::<Scene>();
+    add_simulation(&mut app);
+    app.add_plugin(RngPlugin::new().with_rng_seed(0772))
         .add_plugin(ServerPlugin::new(
             ServerConfig::default(),
             protocol::protocol(),
@@ -608,6 +1027,7 @@ pub fn run() {
                 disconnect_events,
                 error_events,
                 tick_events,
+                scope::update_scopes,
                 spawn_entity_events,
                 despawn_entity_events,
                 insert_component_events,
@@ -618,12 +1038,39 @@ pub fn run() {
                 .in_set(ReceiveEvents),
         )
         // .configure_set(ReceiveEvents.after(PhysicsSet::Writeback))
-        .insert_resource(FixedTime::new_from_secs(constants::TIME_STEP))
-        .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
-            schedule.add_systems((goalie, magnus_effect).after(PhysicsSet::Writeback));
-        })
+        .configure_set(RulesSet.in_set(BeforeReceiveEvents))
         .add_systems(
-            (flush_scores, sync_physics, ball_reset, ball_score).in_set(BeforeReceiveEvents),
+            (
+                flush_scores,
+                shutdown.after(flush_scores),
+                disconnect_rejected,
+                expire_pending,
+                expire_sessions,
+                sync_physics,
+                sync_goalie_state,
+                ball_score.after(RulesSet),
+                lag_compensation::restore_compensated.after(RulesSet),
+                admin::admin_commands.run_if(resource_exists::<AdminConsole>()),
+            )
+                .in_set(BeforeReceiveEvents),
         )
         .run();
+    Ok(())
+}
+
+/// Physics, the goalies and the shared rules.  The goalies, the magnus force and the rules are
+/// held while `Paused`, rapier is stopped by `Paused::set`.
+pub fn add_simulation(app: &mut App) {
+    app.init_resource::<Paused>()
+        .add_plugin(RapierPhysicsPlugin::<ArenaHooks>::default())
+        .insert_resource(FixedTime::new_from_secs(constants::TIME_STEP))
+        .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+            schedule.add_systems(
+                (goalie, magnus_effect)
+                    .after(PhysicsSet::Writeback)
+                    .distributive_run_if(admin::not_paused),
+            );
+        })
+        .add_plugin(RulesPlugin)
+        .configure_set(RulesSet.run_if(admin::not_paused));
 }
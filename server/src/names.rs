@@ -0,0 +1,122 @@
+//! Display name rules.  Names are trimmed and NFC normalized before anything else, and compared
+//! case-folded so "Jason", "jason " and "JASON" are the same name.
+use protocol::primitives::RejectReason;
+
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::Context;
+use bevy::prelude::*;
+use unicode_normalization::UnicodeNormalization;
+
+/// What to do when a name is already in use by someone else
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameCollision {
+    /// Reject with `RejectReason::NameTaken`
+    #[default]
+    Reject,
+    /// Rename to "name 2", "name 3", ...
+    Suffix,
+}
+
+impl FromStr for NameCollision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(NameCollision::Reject),
+            "suffix" => Ok(NameCollision::Suffix),
+            _ => Err(format!("{s}: expected \"reject\" or \"suffix\"")),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct NamePolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Case-folded words that may not appear anywhere in a name
+    pub blocklist: Vec<String>,
+    pub collision: NameCollision,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 1,
+            max_length: 24,
+            blocklist: Vec::new(),
+            collision: NameCollision::default(),
+        }
+    }
+}
+
+pub fn normalize(name: &str) -> String {
+    name.trim().nfc().collect()
+}
+
+/// Key for uniqueness checks, blocklists and anonymous ids.  Full Unicode case folding, so
+/// "STRASSE" and "straße" are the same name.
+pub fn fold(name: &str) -> String {
+    caseless::default_case_fold_str(&normalize(name))
+        .nfc()
+        .collect()
+}
+
+/// One word per line, blank lines and lines starting with `#` are ignored
+pub fn load_blocklist(path: &Path) -> anyhow::Result<Vec<String>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(fold)
+        .collect())
+}
+
+impl NamePolicy {
+    /// Returns the normalized name
+    pub fn validate(&self, name: &str) -> Result<String, RejectReason> {
+        let name = normalize(name);
+        let length = name.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return Err(RejectReason::NameInvalid);
+        }
+        if name.chars().any(char::is_control) {
+            return Err(RejectReason::NameInvalid);
+        }
+
+        let folded = fold(&name);
+        if self.blocklist.iter().any(|word| folded.contains(word)) {
+            return Err(RejectReason::NameInvalid);
+        }
+        Ok(name)
+    }
+
+    /// `taken` is called with case-folded names.  Returns the name to use.
+    pub fn resolve_collision(
+        &self,
+        name: String,
+        taken: impl Fn(&str) -> bool,
+    ) -> Result<String, RejectReason> {
+        if !taken(&fold(&name)) {
+            return Ok(name);
+        }
+        if self.collision == NameCollision::Reject {
+            return Err(RejectReason::NameTaken);
+        }
+
+        for n in 2..100 {
+            let suffix = format!(" {n}");
+            let base: String = name
+                .chars()
+                .take(self.max_length.saturating_sub(suffix.len()))
+                .collect();
+            let candidate = format!("{}{suffix}", base.trim_end());
+            if !taken(&fold(&candidate)) {
+                return Ok(candidate);
+            }
+        }
+        Err(RejectReason::NameTaken)
+    }
+}
@@ -0,0 +1,236 @@
+//! Who is on the server.  Users are `admit`ted when their `Auth` is accepted, connected on their
+//! `ConnectEvent` and forgotten on their `DisconnectEvent`, or kept with `hold` while their
+//! session is.  Admitted users that never connect are dropped by `expire_pending`.  Generic over
+//! the user key so it can be tested without naia.
+use crate::auth::PlayerId;
+use protocol::{components::Player, primitives::RejectReason};
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use bevy::prelude::*;
+use naia_bevy_server::UserKey;
+
+/// Seconds an admitted user has to connect
+pub const PENDING_TIMEOUT: f32 = 30.0;
+
+/// What an admitted user becomes once connected
+pub enum Pending {
+    /// Gets a new ball in the room with the given code
+    Player {
+        player: Player,
+        player_id: PlayerId,
+        room: String,
+    },
+    /// Watches the room with the given code
+    Spectator { room: String },
+    /// Plays the goalie of the room with the given code, watching like a spectator
+    Goalie { room: String },
+    /// Gets the ball of a held `sessions::Session` back
+    Resume { token: String },
+    /// Is sent `Rejected` and disconnected
+    Rejected(RejectReason),
+}
+
+#[derive(Resource)]
+pub struct PlayerRegistry<K: Copy + Eq + Hash + Send + Sync + 'static = UserKey> {
+    /// With the seconds since they were admitted
+    pending: HashMap<K, (Pending, f32)>,
+    player_to_entity: HashMap<K, Entity>,
+    entity_to_player: HashMap<Entity, K>,
+    spectators: HashSet<K>,
+    /// Users playing a room's goalie, they watch like `spectators` but take a player's slot
+    goalies: HashSet<K>,
+    /// Dropped players whose ball is kept for them, they still own it
+    held: HashSet<K>,
+}
+
+impl<K: Copy + Eq + Hash + Send + Sync + 'static> Default for PlayerRegistry<K> {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+            player_to_entity: HashMap::new(),
+            entity_to_player: HashMap::new(),
+            spectators: HashSet::new(),
+            goalies: HashSet::new(),
+            held: HashSet::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash + Send + Sync + 'static> PlayerRegistry<K> {
+    /// Replaces an earlier admission of the same user
+    pub fn admit(&mut self, user_key: K, pending: Pending) {
+        self.pending.insert(user_key, (pending, 0.0));
+    }
+
+    /// Called on connect, `None` if the user wasn't admitted or timed out
+    pub fn take_pending(&mut self, user_key: &K) -> Option<Pending> {
+        self.pending.remove(user_key).map(|(pending, _)| pending)
+    }
+
+    pub fn connect_player(&mut self, user_key: K, ball: Entity) {
+        if let Some(old_ball) = self.player_to_entity.insert(user_key, ball) {
+            self.entity_to_player.remove(&old_ball);
+        }
+        if let Some(old_owner) = self.entity_to_player.insert(ball, user_key) {
+            if old_owner != user_key {
+                self.player_to_entity.remove(&old_owner);
+                self.held.remove(&old_owner);
+            }
+        }
+        self.spectators.remove(&user_key);
+        self.goalies.remove(&user_key);
+    }
+
+    pub fn connect_spectator(&mut self, user_key: K) {
+        if let Some(ball) = self.player_to_entity.remove(&user_key) {
+            self.entity_to_player.remove(&ball);
+        }
+        self.goalies.remove(&user_key);
+        self.spectators.insert(user_key);
+    }
+
+    pub fn connect_goalie(&mut self, user_key: K) {
+        self.connect_spectator(user_key);
+        self.goalies.insert(user_key);
+    }
+
+    /// The user dropped but keeps their ball until `connect_player` hands it to the user that
+    /// resumed, or `disconnect` forgets them.  Returns their ball.
+    pub fn hold(&mut self, user_key: K) -> Option<Entity> {
+        self.pending.remove(&user_key);
+        self.spectators.remove(&user_key);
+        self.goalies.remove(&user_key);
+        let ball = self.ball_of(&user_key)?;
+        self.held.insert(user_key);
+        Some(ball)
+    }
+
+    /// Forgets the user, returns their ball
+    pub fn disconnect(&mut self, user_key: &K) -> Option<Entity> {
+        self.pending.remove(user_key);
+        self.spectators.remove(user_key);
+        self.goalies.remove(user_key);
+        self.held.remove(user_key);
+        let ball = self.player_to_entity.remove(user_key)?;
+        self.entity_to_player.remove(&ball);
+        Some(ball)
+    }
+
+    /// Drops users admitted more than `PENDING_TIMEOUT` seconds ago and returns them
+    pub fn expire_pending(&mut self, dt: f32) -> Vec<K> {
+        let mut expired = Vec::new();
+        self.pending.retain(|user_key, (_, seconds)| {
+            *seconds += dt;
+            if *seconds <= PENDING_TIMEOUT {
+                return true;
+            }
+            expired.push(*user_key);
+            false
+        });
+        expired
+    }
+
+    pub fn ball_of(&self, user_key: &K) -> Option<Entity> {
+        self.player_to_entity.get(user_key).copied()
+    }
+
+    pub fn owner_of(&self, ball: Entity) -> Option<K> {
+        self.entity_to_player.get(&ball).copied()
+    }
+
+    pub fn is_spectator(&self, user_key: &K) -> bool {
+        self.spectators.contains(user_key)
+    }
+
+    pub fn is_pending(&self, user_key: &K) -> bool {
+        self.pending.contains_key(user_key)
+    }
+
+    pub fn is_goalie(&self, user_key: &K) -> bool {
+        self.goalies.contains(user_key)
+    }
+
+    pub fn is_held(&self, user_key: &K) -> bool {
+        self.held.contains(user_key)
+    }
+
+    /// Connected players and their balls
+    pub fn players(&self) -> impl Iterator<Item = (K, Entity)> + '_ {
+        self.player_to_entity
+            .iter()
+            .filter(|(user_key, _)| !self.held.contains(user_key))
+            .map(|(user_key, ball)| (*user_key, *ball))
+    }
+
+    /// Admitted players that haven't connected yet, they count towards names and `max_players`
+    pub fn pending_players(&self) -> impl Iterator<Item = (&Player, &PlayerId)> + '_ {
+        self.pending
+            .values()
+            .filter_map(|(pending, _)| match pending {
+                Pending::Player {
+                    player, player_id, ..
+                } => Some((player, player_id)),
+                _ => None,
+            })
+    }
+
+    /// Connected players, goalies included
+    pub fn player_count(&self) -> usize {
+        self.player_to_entity.len() - self.held.len() + self.goalies.len()
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.spectators.len() - self.goalies.len()
+    }
+
+    /// Slots counted against `max_players`: connected and held players, goalies and the players
+    /// and goalies that were admitted but haven't connected yet
+    pub fn slots_taken(&self) -> usize {
+        let pending = self
+            .pending
+            .values()
+            .filter(|(pending, _)| {
+                matches!(pending, Pending::Player { .. } | Pending::Goalie { .. })
+            })
+            .count();
+        self.player_to_entity.len() + self.goalies.len() + pending
+    }
+
+    /// Panics if the maps disagree with each other
+    pub fn assert_invariants(&self) {
+        assert_eq!(self.player_to_entity.len(), self.entity_to_player.len());
+        for (user_key, ball) in &self.player_to_entity {
+            assert!(
+                self.entity_to_player.get(ball) == Some(user_key),
+                "ball of a player is owned by someone else"
+            );
+            assert!(
+                !self.spectators.contains(user_key),
+                "player is also a spectator"
+            );
+        }
+        for user_key in &self.goalies {
+            assert!(
+                self.spectators.contains(user_key),
+                "goalie doesn't watch like a spectator"
+            );
+        }
+        for user_key in &self.held {
+            assert!(
+                self.player_to_entity.contains_key(user_key),
+                "held user has no ball"
+            );
+        }
+        for user_key in self.pending.keys() {
+            assert!(
+                !self.player_to_entity.contains_key(user_key)
+                    && !self.spectators.contains(user_key),
+                "pending user is already connected"
+            );
+        }
+    }
+}
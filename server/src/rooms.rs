@@ -0,0 +1,231 @@
+//! Matches.  Each room has its own naia room, goal, goalie and tally and its own `Arena` in the
+//! shared physics world.  Players pick a room with `Auth::room`, rooms are created on demand and
+//! destroyed when the last player leaves, except for the main room which always exists.
+use core::{
+    components::{Arena, GoalieBehavior, GoalieControl},
+    constants, field,
+    goalie_ai::Difficulty,
+};
+use protocol::components::{EntityKind, GoalieState, RepPhysics};
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use naia_bevy_server::{CommandsExt, RoomKey, Server, UserKey};
+use naia_bevy_shared::{Channel, Message};
+
+pub const MAIN_ROOM: &str = "main";
+const MAX_ROOM_CODE_LENGTH: usize = 16;
+
+/// Lowercase ascii letters, digits and `-`.  Anything else, or no code at all, is the main room.
+pub fn room_code(code: Option<&str>) -> String {
+    let code = code.unwrap_or_default().trim().to_lowercase();
+    let valid = !code.is_empty()
+        && code.len() <= MAX_ROOM_CODE_LENGTH
+        && code
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        code
+    } else {
+        MAIN_ROOM.to_owned()
+    }
+}
+
+pub struct Room {
+    pub code: String,
+    pub key: RoomKey,
+    pub arena: Arena,
+    pub goal_entity: Entity,
+    pub goalie_entity: Entity,
+    pub difficulty: Difficulty,
+    /// The user playing the goalie instead of its brain
+    pub keeper: Option<UserKey>,
+    pub users: HashSet<UserKey>,
+    pub blue_total: u32,
+    pub pink_total: u32,
+}
+
+impl Room {
+    /// Hands the goalie to the user.  `false` if someone else plays it already.
+    pub fn take_goalie(&mut self, commands: &mut Commands, user_key: UserKey) -> bool {
+        if self.keeper.is_some() {
+            return false;
+        }
+        self.keeper = Some(user_key);
+        commands
+            .entity(self.goalie_entity)
+            .insert(GoalieControl::default());
+        true
+    }
+
+    pub fn broadcast<C: Channel, M: Message>(&self, server: &mut Server, message: &M) {
+        for user_key in &self.users {
+            server.send_message::<C, M>(user_key, message);
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct RoomManager {
+    rooms: HashMap<String, Room>,
+    user_rooms: HashMap<UserKey, String>,
+    next_arena: u32,
+    /// Of the goalies in new rooms
+    difficulty: Difficulty,
+}
+
+impl RoomManager {
+    /// Creates the main room, starting its tally at the persisted totals
+    pub fn new(
+        commands: &mut Commands,
+        server: &mut Server,
+        blue_total: u32,
+        pink_total: u32,
+        difficulty: Difficulty,
+    ) -> Self {
+        let mut rooms = Self {
+            rooms: HashMap::new(),
+            user_rooms: HashMap::new(),
+            next_arena: 0,
+            difficulty,
+        };
+        let main = rooms.get_or_create(commands, server, MAIN_ROOM);
+        main.blue_total = blue_total;
+        main.pink_total = pink_total;
+        rooms
+    }
+
+    fn get_or_create(
+        &mut self,
+        commands: &mut Commands,
+        server: &mut Server,
+        code: &str,
+    ) -> &mut Room {
+        if !self.rooms.contains_key(code) {
+            let arena = Arena(self.next_arena);
+            self.next_arena += 1;
+            let key = server.make_room().key();
+            let (goal_entity, goalie_entity) =
+                spawn_field(commands, server, &key, arena, self.difficulty);
+            info!("created room {} ({:?})", code, arena);
+
+            self.rooms.insert(
+                code.to_owned(),
+                Room {
+                    code: code.to_owned(),
+                    key,
+                    arena,
+                    goal_entity,
+                    goalie_entity,
+                    difficulty: self.difficulty,
+                    keeper: None,
+                    users: HashSet::new(),
+                    blue_total: 0,
+                    pink_total: 0,
+                },
+            );
+        }
+        self.rooms.get_mut(code).expect("inserted above. qed")
+    }
+
+    /// Puts the user in the room with the given code, creating it if needed
+    pub fn join(
+        &mut self,
+        commands: &mut Commands,
+        server: &mut Server,
+        code: &str,
+        user_key: UserKey,
+    ) -> &mut Room {
+        self.user_rooms.insert(user_key, code.to_owned());
+        let room = self.get_or_create(commands, server, code);
+        room.users.insert(user_key);
+        server.user_mut(&user_key).enter_room(&room.key);
+        room
+    }
+
+    /// Destroys the room if the user was the last one in it
+    pub fn leave(&mut self, commands: &mut Commands, server: &mut Server, user_key: &UserKey) {
+        let Some(code) = self.user_rooms.remove(user_key) else {
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(&code) else {
+            return;
+        };
+        room.users.remove(user_key);
+        if room.keeper == Some(*user_key) {
+            info!("the goalie of room {} is a bot again", room.code);
+            room.keeper = None;
+            commands
+                .entity(room.goalie_entity)
+                .remove::<GoalieControl>();
+        }
+        if !room.users.is_empty() || code == MAIN_ROOM {
+            return;
+        }
+
+        let room = self.rooms.remove(&code).expect("checked above. qed");
+        info!("destroying empty room {}", room.code);
+        server.room_mut(&room.key).destroy();
+        commands.entity(room.goalie_entity).despawn();
+        commands.entity(room.goal_entity).despawn_recursive();
+    }
+
+    /// Spectators and goalies can only join rooms that players have created
+    pub fn exists(&self, code: &str) -> bool {
+        self.rooms.contains_key(code)
+    }
+
+    pub fn of_user(&self, user_key: &UserKey) -> Option<&Room> {
+        self.rooms.get(self.user_rooms.get(user_key)?)
+    }
+
+    pub fn get_mut(&mut self, code: &str) -> Option<&mut Room> {
+        self.rooms.get_mut(code)
+    }
+
+    pub fn of_arena_mut(&mut self, arena: Arena) -> Option<&mut Room> {
+        self.rooms.values_mut().find(|room| room.arena == arena)
+    }
+
+    pub fn of_arena(&self, arena: Arena) -> Option<&Room> {
+        self.rooms.values().find(|room| room.arena == arena)
+    }
+
+    pub fn main_mut(&mut self) -> &mut Room {
+        self.rooms
+            .get_mut(MAIN_ROOM)
+            .expect("the main room always exists. qed")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Room> {
+        self.rooms.values()
+    }
+}
+
+/// Goal and goalie of a room.  Returns `(goal, goalie)`.
+fn spawn_field(
+    commands: &mut Commands,
+    server: &mut Server,
+    room_key: &RoomKey,
+    arena: Arena,
+    difficulty: Difficulty,
+) -> (Entity, Entity) {
+    let goal = field::spawn_goal(commands, arena);
+
+    let goalie_transform = Transform::from_translation(constants::GOALIE_START);
+    let goalie = commands
+        .spawn(field::goalie_body(arena, constants::GOALIE_START))
+        .insert((
+            EntityKind::goalie(),
+            GoalieState::standing(),
+            GoalieBehavior::new(difficulty),
+            RepPhysics::new_without_velocity(&goalie_transform),
+        ))
+        .enable_replication(server)
+        .id();
+
+    server.room_mut(room_key).add_entity(&goalie);
+
+    (goal, goalie)
+}
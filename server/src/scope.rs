@@ -0,0 +1,111 @@
+//! Interest management.  Decides which balls each user gets `RepPhysics` updates for.  The user's
+//! own ball and the goalie are always in scope and spectators get everything.  naia's rooms
+//! already keep other matches out.
+use crate::{registry::PlayerRegistry, settings::ServerSettings};
+use core::components::{Arena, Ball};
+
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use bevy::prelude::*;
+use naia_bevy_server::{events::TickEvent, Server};
+
+/// Amount of balls `recent` keeps in scope when no count is given
+const DEFAULT_RECENT: usize = 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScopePolicy {
+    /// Every ball in the room
+    #[default]
+    All,
+    /// The `n` most recently kicked balls in the room
+    Recent(usize),
+    /// Balls that have been kicked and haven't been reset yet
+    InFlight,
+}
+
+impl FromStr for ScopePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "all" => Ok(ScopePolicy::All),
+            None if s == "in-flight" => Ok(ScopePolicy::InFlight),
+            None if s == "recent" => Ok(ScopePolicy::Recent(DEFAULT_RECENT)),
+            Some(("recent", n)) => n
+                .parse()
+                .map(ScopePolicy::Recent)
+                .map_err(|err| format!("{s}: {err}")),
+            _ => Err(format!(
+                "{s}: expected \"all\", \"in-flight\", \"recent\" or \"recent:<count>\""
+            )),
+        }
+    }
+}
+
+/// When the ball was last kicked, in seconds since startup
+#[derive(Component)]
+pub struct LastKick(pub f64);
+
+pub fn update_scopes(
+    mut server: Server,
+    registry: Res<PlayerRegistry>,
+    settings: Res<ServerSettings>,
+    ball_query: Query<(Entity, &Ball, &Arena, Option<&LastKick>)>,
+    mut tick_reader: EventReader<TickEvent>,
+) {
+    if tick_reader.is_empty() {
+        return;
+    }
+    tick_reader.clear();
+
+    let recent = match settings.scope {
+        ScopePolicy::Recent(n) => most_recent(&ball_query, n),
+        _ => HashSet::new(),
+    };
+
+    for (_, user_key, entity) in server.scope_checks() {
+        let in_scope = match ball_query.get(entity) {
+            Ok((_, ball, ..)) => {
+                registry.ball_of(&user_key) == Some(entity)
+                    || registry.is_spectator(&user_key)
+                    || match settings.scope {
+                        ScopePolicy::All => true,
+                        ScopePolicy::Recent(_) => recent.contains(&entity),
+                        ScopePolicy::InFlight => ball.shot,
+                    }
+            }
+            // the goalie
+            Err(_) => true,
+        };
+
+        if in_scope {
+            server.user_scope(&user_key).include(&entity);
+        } else {
+            server.user_scope(&user_key).exclude(&entity);
+        }
+    }
+}
+
+/// The `n` most recently kicked balls of every arena
+fn most_recent(
+    ball_query: &Query<(Entity, &Ball, &Arena, Option<&LastKick>)>,
+    n: usize,
+) -> HashSet<Entity> {
+    let mut kicks: HashMap<Arena, Vec<(f64, Entity)>> = HashMap::new();
+    for (entity, _, arena, last_kick) in ball_query.iter() {
+        if let Some(LastKick(at)) = last_kick {
+            kicks.entry(*arena).or_default().push((*at, entity));
+        }
+    }
+
+    kicks
+        .into_values()
+        .flat_map(|mut kicks| {
+            kicks.sort_by(|a, b| b.0.total_cmp(&a.0));
+            kicks.into_iter().take(n).map(|(_, entity)| entity)
+        })
+        .collect()
+}
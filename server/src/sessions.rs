@@ -0,0 +1,119 @@
+//! Session resume.  Players get a `Session` token with their ball.  When their connection drops
+//! the ball stays in its room for `ServerSettings::resume_grace`, and a client reconnecting with
+//! the token in `Auth::resume` gets the same ball back.  Scores are keyed by `PlayerId` so nothing
+//! else has to be carried over.  Generic over the user key so it can be tested without naia.
+use crate::auth::PlayerId;
+
+use std::{collections::HashMap, hash::Hash};
+
+use bevy::prelude::*;
+use naia_bevy_server::UserKey;
+
+#[derive(Clone)]
+pub struct Session<K = UserKey> {
+    pub ball: Entity,
+    pub player_id: PlayerId,
+    /// The connected user, or the one that dropped
+    pub user_key: K,
+    /// Seconds since the user dropped, `None` while connected
+    pub disconnected_for: Option<f32>,
+}
+
+#[derive(Resource)]
+pub struct Sessions<K: Copy + Eq + Hash + Send + Sync + 'static = UserKey> {
+    sessions: HashMap<String, Session<K>>,
+}
+
+impl<K: Copy + Eq + Hash + Send + Sync + 'static> Default for Sessions<K> {
+    fn default() -> Self {
+        Self {
+            sessions: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash + Send + Sync + 'static> Sessions<K> {
+    /// Returns the token for `Session`
+    pub fn start(&mut self, user_key: K, ball: Entity, player_id: PlayerId) -> String {
+        let token = new_token();
+        self.sessions.insert(
+            token.clone(),
+            Session {
+                ball,
+                player_id,
+                user_key,
+                disconnected_for: None,
+            },
+        );
+        token
+    }
+
+    /// Forgets the user's session, e.g. when they are kicked
+    pub fn end(&mut self, user_key: &K) {
+        self.sessions
+            .retain(|_, session| session.user_key != *user_key);
+    }
+
+    /// Keeps the dropped user's ball around.  `false` if they had no session.
+    pub fn hold(&mut self, user_key: &K) -> bool {
+        match self
+            .sessions
+            .values_mut()
+            .find(|session| session.user_key == *user_key)
+        {
+            Some(session) => {
+                session.disconnected_for = Some(0.0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The held session for `token`, if it is still waiting for its user
+    pub fn held(&self, token: &str) -> Option<&Session<K>> {
+        self.sessions
+            .get(token)
+            .filter(|session| session.disconnected_for.is_some())
+    }
+
+    /// Hands a held session to the reconnected user.  Returns the session with the user key that
+    /// dropped.
+    pub fn resume(&mut self, token: &str, user_key: K) -> Option<Session<K>> {
+        let session = self
+            .sessions
+            .get_mut(token)
+            .filter(|session| session.disconnected_for.is_some())?;
+        let dropped = session.clone();
+        session.user_key = user_key;
+        session.disconnected_for = None;
+        Some(dropped)
+    }
+
+    /// Removes and returns the sessions held for longer than `grace` seconds
+    pub fn expire(&mut self, dt: f32, grace: f32) -> Vec<Session<K>> {
+        let mut expired = Vec::new();
+        for session in self.sessions.values_mut() {
+            if let Some(disconnected_for) = session.disconnected_for.as_mut() {
+                *disconnected_for += dt;
+            }
+        }
+        self.sessions.retain(|_, session| {
+            if session
+                .disconnected_for
+                .map_or(true, |seconds| seconds <= grace)
+            {
+                return true;
+            }
+            expired.push(session.clone());
+            false
+        });
+        expired
+    }
+}
+
+/// 128 bits from the OS as hex, whoever has the token gets the ball and the scores
+fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("the OS has no random source");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,60 @@
+use crate::{names::NameCollision, scope::ScopePolicy};
+use core::goalie_ai::Difficulty;
+
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use bevy::prelude::*;
+
+/// Where the server listens and what it advertises to clients.  Filled in from the command line
+/// / environment by the binary, the defaults match a local development setup.
+#[derive(Resource, Clone, Debug)]
+pub struct ServerSettings {
+    /// Address/port the WebRTC signaling (handshake) server listens on
+    pub signal_addr: SocketAddr,
+    /// Address/port to listen on for UDP WebRTC data channels
+    pub data_addr: SocketAddr,
+    /// The public WebRTC data address to advertise, must be reachable by clients
+    pub public_url: String,
+    /// Score store, a JSON file path or `sqlite:<path>`.  See `store::open`
+    pub store: String,
+    /// Save the scores on every goal instead of every 30 seconds
+    pub write_through: bool,
+    /// Shared secret for player tokens, players are anonymous without it
+    pub auth_secret: Option<String>,
+    /// Reject players without a token, only used with `auth_secret`
+    pub require_token: bool,
+    /// Players past this are rejected as `ServerFull`
+    pub max_players: usize,
+    /// Words players can't use in their names, see `names::load_blocklist`
+    pub name_blocklist: Option<PathBuf>,
+    pub name_collision: NameCollision,
+    /// Which balls each player receives updates for
+    pub scope: ScopePolicy,
+    /// How long a dropped player's ball waits for them to reconnect, see `sessions`
+    pub resume_grace: Duration,
+    /// Read `admin::AdminCommand`s from stdin
+    pub admin_console: bool,
+    /// Of the goalies in new rooms, the admin console can change it per room
+    pub goalie: Difficulty,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            signal_addr: SocketAddr::from(([0, 0, 0, 0], 14191)),
+            data_addr: SocketAddr::from(([0, 0, 0, 0], 14192)),
+            public_url: protocol::SERVER_AD_URL.to_string(),
+            store: "./powerbaby.json".to_string(),
+            write_through: false,
+            auth_secret: None,
+            require_token: false,
+            max_players: 64,
+            name_blocklist: None,
+            name_collision: NameCollision::default(),
+            scope: ScopePolicy::default(),
+            resume_grace: Duration::from_secs(30),
+            admin_console: false,
+            goalie: Difficulty::default(),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! Score persistence.
+//!
+//! `open` picks a backend from the `--store` setting, a plain path is a JSON file and a
+//! `sqlite:` prefixed path is an embedded SQLite database (needs the `sqlite` feature).
+use protocol::primitives::Scores;
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bevy::prelude::*;
+
+/// Amount of rotated backups the JSON store keeps next to the scores file
+pub const JSON_BACKUPS: usize = 3;
+
+pub trait ScoreStore: Send + Sync {
+    /// Returns the default `Scores` if nothing has been stored yet
+    fn load(&self) -> anyhow::Result<Scores>;
+    fn save(&self, scores: &Scores) -> anyhow::Result<()>;
+}
+
+#[derive(Resource)]
+pub struct ScoreStorage(pub Box<dyn ScoreStore>);
+
+pub fn open(store: &str) -> anyhow::Result<Box<dyn ScoreStore>> {
+    if let Some(path) = store.strip_prefix("sqlite:") {
+        return open_sqlite(path);
+    }
+    Ok(Box::new(JsonFileStore::new(store, JSON_BACKUPS)))
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &str) -> anyhow::Result<Box<dyn ScoreStore>> {
+    Ok(Box::new(SqliteStore::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(path: &str) -> anyhow::Result<Box<dyn ScoreStore>> {
+    Err(anyhow::anyhow!(
+        "{path}: server was built without the \"sqlite\" feature"
+    ))
+}
+
+/// Scores as pretty JSON.  Saves go to a temp file that is renamed over the real one, so a full
+/// disk or a crash mid-write leaves the previous file intact.  The replaced files are kept as
+/// `<path>.1` (newest) to `<path>.<backups>`.
+pub struct JsonFileStore {
+    path: PathBuf,
+    backups: usize,
+}
+
+impl JsonFileStore {
+    pub fn new(path: impl Into<PathBuf>, backups: usize) -> Self {
+        Self {
+            path: path.into(),
+            backups,
+        }
+    }
+
+    fn backup_path(&self, n: usize) -> PathBuf {
+        with_suffix(&self.path, &n.to_string())
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Scores> {
+        let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    fn rotate(&self) -> anyhow::Result<()> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+        for n in (1..self.backups).rev() {
+            let from = self.backup_path(n);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(n + 1))
+                    .with_context(|| format!("rotating {}", from.display()))?;
+            }
+        }
+        fs::copy(&self.path, self.backup_path(1))
+            .with_context(|| format!("backing up {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl ScoreStore for JsonFileStore {
+    fn load(&self) -> anyhow::Result<Scores> {
+        if !self.path.exists() {
+            info!(
+                "{} doesn't exist, starting with empty scores",
+                self.path.display()
+            );
+            return Ok(Scores::default());
+        }
+
+        let err = match Self::read(&self.path) {
+            Ok(scores) => return Ok(scores),
+            Err(err) => err,
+        };
+        error!("{err:#}");
+
+        // fall back to the newest readable backup
+        for n in 1..=self.backups {
+            let backup = self.backup_path(n);
+            if !backup.exists() {
+                continue;
+            }
+            match Self::read(&backup) {
+                Ok(scores) => {
+                    warn!("recovered scores from {}", backup.display());
+                    return Ok(scores);
+                }
+                Err(err) => error!("{err:#}"),
+            }
+        }
+        Err(err.context("no readable scores or backups"))
+    }
+
+    fn save(&self, scores: &Scores) -> anyhow::Result<()> {
+        let bytes = serde_json::to_vec_pretty(scores)?;
+        let tmp = with_suffix(&self.path, "tmp");
+
+        let write = || -> anyhow::Result<()> {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            Ok(())
+        };
+        if let Err(err) = write() {
+            let _ = fs::remove_file(&tmp);
+            return Err(err.context(format!("writing {}", tmp.display())));
+        }
+
+        if let Err(err) = self.rotate() {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("renaming {} to {}", tmp.display(), self.path.display()))
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::ScoreStore;
+    use protocol::primitives::{PlayerStats, Scores};
+
+    use std::{collections::HashMap, path::Path, sync::Mutex};
+
+    use anyhow::Context;
+    use rusqlite::{params, Connection};
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS totals (
+            color TEXT PRIMARY KEY,
+            goals INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS names (
+            player_id TEXT PRIMARY KEY,
+            name TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS personal (
+            color TEXT NOT NULL,
+            player_id TEXT NOT NULL,
+            goals INTEGER NOT NULL,
+            PRIMARY KEY (color, player_id)
+        );
+        CREATE TABLE IF NOT EXISTS stats (
+            player_id TEXT PRIMARY KEY,
+            kicks INTEGER NOT NULL,
+            goals INTEGER NOT NULL,
+            goalie_denies INTEGER NOT NULL,
+            frame_denies INTEGER NOT NULL,
+            current_streak INTEGER NOT NULL,
+            longest_streak INTEGER NOT NULL,
+            unscored_kick INTEGER NOT NULL,
+            first_seen INTEGER NOT NULL,
+            last_seen INTEGER NOT NULL
+        );
+    ";
+
+    /// Each save replaces the stored scores inside a single transaction
+    pub struct SqliteStore {
+        conn: Mutex<Connection>,
+    }
+
+    impl SqliteStore {
+        pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+            let path = path.as_ref();
+            let conn =
+                Connection::open(path).with_context(|| format!("opening {}", path.display()))?;
+            conn.execute_batch(SCHEMA)?;
+            // databases from before player ids kept them in a `name` column
+            for table in ["personal", "stats"] {
+                let legacy: bool = conn.query_row(
+                    "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = 'name'",
+                    [table],
+                    |row| row.get(0),
+                )?;
+                if legacy {
+                    conn.execute_batch(&format!(
+                        "ALTER TABLE {table} RENAME COLUMN name TO player_id"
+                    ))?;
+                }
+            }
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+    }
+
+    impl ScoreStore for SqliteStore {
+        fn load(&self) -> anyhow::Result<Scores> {
+            let conn = self.conn.lock().expect("sqlite connection poisoned");
+            let mut scores = Scores::default();
+
+            let version =
+                conn.query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+                    row.get(0)
+                });
+            scores.version = match version {
+                Ok(version) => version,
+                Err(rusqlite::Error::QueryReturnedNoRows) => 0,
+                Err(err) => return Err(err.into()),
+            };
+
+            let mut stmt = conn.prepare("SELECT color, goals FROM totals")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let color: String = row.get(0)?;
+                let goals: u32 = row.get(1)?;
+                match color.as_str() {
+                    "blue" => scores.blue_total = goals,
+                    "pink" => scores.pink_total = goals,
+                    _ => {}
+                }
+            }
+
+            let mut stmt = conn.prepare("SELECT color, player_id, goals FROM personal")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let color: String = row.get(0)?;
+                let personal = match color.as_str() {
+                    "blue" => &mut scores.personal_blue,
+                    "pink" => &mut scores.personal_pink,
+                    _ => continue,
+                };
+                personal.insert(row.get(1)?, row.get(2)?);
+            }
+
+            let mut stmt = conn.prepare("SELECT player_id, name FROM names")?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                scores.names.insert(row.get(0)?, row.get(1)?);
+            }
+
+            let mut stmt = conn.prepare(
+                "SELECT player_id, kicks, goals, goalie_denies, frame_denies, current_streak,
+                 longest_streak, unscored_kick, first_seen, last_seen FROM stats",
+            )?;
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let stats = PlayerStats {
+                    kicks: row.get(1)?,
+                    goals: row.get(2)?,
+                    goalie_denies: row.get(3)?,
+                    frame_denies: row.get(4)?,
+                    current_streak: row.get(5)?,
+                    longest_streak: row.get(6)?,
+                    unscored_kick: row.get(7)?,
+                    first_seen: row.get::<_, i64>(8)? as u64,
+                    last_seen: row.get::<_, i64>(9)? as u64,
+                };
+                scores.stats.insert(row.get(0)?, stats);
+            }
+            Ok(scores)
+        }
+
+        fn save(&self, scores: &Scores) -> anyhow::Result<()> {
+            let mut conn = self.conn.lock().expect("sqlite connection poisoned");
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('version', ?1)",
+                params![scores.version],
+            )?;
+            tx.execute("DELETE FROM totals", [])?;
+            tx.execute("DELETE FROM personal", [])?;
+            tx.execute("DELETE FROM stats", [])?;
+            tx.execute("DELETE FROM names", [])?;
+            tx.execute(
+                "INSERT INTO totals (color, goals) VALUES ('blue', ?1), ('pink', ?2)",
+                params![scores.blue_total, scores.pink_total],
+            )?;
+            {
+                let mut insert = tx.prepare(
+                    "INSERT INTO personal (color, player_id, goals) VALUES (?1, ?2, ?3)",
+                )?;
+                let personal: [(&str, &HashMap<String, u32>); 2] = [
+                    ("blue", &scores.personal_blue),
+                    ("pink", &scores.personal_pink),
+                ];
+                for (color, goals) in personal {
+                    for (player_id, goals) in goals {
+                        insert.execute(params![color, player_id, goals])?;
+                    }
+                }
+
+                let mut insert =
+                    tx.prepare("INSERT INTO names (player_id, name) VALUES (?1, ?2)")?;
+                for (player_id, name) in &scores.names {
+                    insert.execute(params![player_id, name])?;
+                }
+
+                let mut insert = tx.prepare(
+                    "INSERT INTO stats (player_id, kicks, goals, goalie_denies, frame_denies,
+                     current_streak, longest_streak, unscored_kick, first_seen, last_seen)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )?;
+                for (player_id, stats) in &scores.stats {
+                    insert.execute(params![
+                        player_id,
+                        stats.kicks,
+                        stats.goals,
+                        stats.goalie_denies,
+                        stats.frame_denies,
+                        stats.current_streak,
+                        stats.longest_streak,
+                        stats.unscored_kick,
+                        stats.first_seen as i64,
+                        stats.last_seen as i64,
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        }
+    }
+}
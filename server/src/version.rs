@@ -0,0 +1,18 @@
+//! Which clients this server talks to.  Other clients are rejected with
+//! `RejectReason::VersionMismatch`, which tells players to refresh their (cached) wasm build.
+use protocol::{
+    primitives::{ProtocolVersion, ServerFeature},
+    PROTOCOL_VERSION,
+};
+
+pub const FEATURES: &[ServerFeature] = &[
+    ServerFeature::LagCompensation,
+    ServerFeature::Prediction,
+    ServerFeature::Stats,
+];
+
+/// Same major version and not newer than the server, see `PROTOCOL_VERSION` for what a major
+/// bump means.  Newer clients may send messages this server doesn't know.
+pub fn is_compatible(client: &ProtocolVersion) -> bool {
+    client.major == PROTOCOL_VERSION.major && *client <= PROTOCOL_VERSION
+}
@@ -0,0 +1,208 @@
+use powerbaby_server::{
+    add_simulation,
+    admin::{offline_player, AdminCommand, Paused},
+    auth::PlayerId,
+};
+
+use core::{
+    components::{Arena, Ball, GoalieBehavior},
+    constants::*,
+    field,
+    goalie_ai::Difficulty,
+    rules::KickModel,
+};
+use protocol::primitives::{PlayColor, Scores};
+
+use std::time::{Duration, Instant};
+
+use bevy::asset::AssetPlugin;
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use bevy::scene::ScenePlugin;
+use bevy::time::{TimePlugin, TimeUpdateStrategy};
+use bevy_rapier3d::prelude::*;
+use bevy_turborand::prelude::*;
+
+fn parse(line: &str) -> Result<AdminCommand, String> {
+    line.parse()
+}
+
+#[test]
+fn commands_without_arguments() {
+    assert_eq!(parse("list"), Ok(AdminCommand::List));
+    assert_eq!(parse("  scores "), Ok(AdminCommand::Scores));
+    assert_eq!(parse("scores reset"), Ok(AdminCommand::ResetScores));
+    assert_eq!(parse("pause"), Ok(AdminCommand::Pause));
+    assert_eq!(parse("resume"), Ok(AdminCommand::Resume));
+    assert_eq!(parse("help"), Ok(AdminCommand::Help));
+    assert!(parse("list everyone").is_err());
+    assert!(parse("dance").is_err());
+}
+
+#[test]
+fn player_commands_keep_the_whole_name() {
+    assert_eq!(
+        parse("kick Jason 2"),
+        Ok(AdminCommand::Kick("Jason 2".to_owned()))
+    );
+    assert_eq!(
+        parse("ban   id:alice "),
+        Ok(AdminCommand::Ban("id:alice".to_owned()))
+    );
+    assert_eq!(
+        parse("unban anon:jason"),
+        Ok(AdminCommand::Unban("anon:jason".to_owned()))
+    );
+    assert!(parse("kick").is_err());
+    assert!(parse("ban  ").is_err());
+}
+
+#[test]
+fn set_total_needs_a_team_and_a_number() {
+    assert_eq!(
+        parse("scores blue 12"),
+        Ok(AdminCommand::SetTotal(PlayColor::Blue, 12))
+    );
+    assert_eq!(
+        parse("scores pink  0"),
+        Ok(AdminCommand::SetTotal(PlayColor::Pink, 0))
+    );
+    assert!(parse("scores green 1").is_err());
+    assert!(parse("scores blue -1").is_err());
+    assert!(parse("scores blue").is_err());
+}
+
+#[test]
+fn announce_may_be_empty() {
+    assert_eq!(
+        parse("announce back in 5"),
+        Ok(AdminCommand::Announce("back in 5".to_owned()))
+    );
+    assert_eq!(parse("announce"), Ok(AdminCommand::Announce(String::new())));
+}
+
+#[test]
+fn goalie_takes_a_room_and_a_level() {
+    assert_eq!(
+        parse("goalie abc123 hard"),
+        Ok(AdminCommand::Goalie("abc123".to_owned(), Difficulty::Hard))
+    );
+    assert!(parse("goalie abc123").is_err());
+    assert!(parse("goalie abc123 impossible").is_err());
+}
+
+#[test]
+fn offline_players_resolve_to_ids() {
+    let mut scores = Scores::default();
+    scores
+        .names
+        .insert("id:alice".to_owned(), "Alice".to_owned());
+    scores.names.insert("anon:sam".to_owned(), "Sam".to_owned());
+    scores.names.insert("id:sam".to_owned(), "sam".to_owned());
+
+    assert_eq!(
+        offline_player(&scores, "id:bob"),
+        Ok(PlayerId("id:bob".to_owned()))
+    );
+    assert_eq!(
+        offline_player(&scores, " ALICE"),
+        Ok(PlayerId("id:alice".to_owned()))
+    );
+    // never seen, banned as the anonymous player they would be
+    assert_eq!(
+        offline_player(&scores, "Jason"),
+        Ok(PlayerId::anonymous("Jason"))
+    );
+    assert!(offline_player(&scores, "Sam").is_err());
+}
+
+/// Steps the app by `TIME_STEP` like the server loop would
+fn step(app: &mut App, now: &mut Instant) {
+    *now += Duration::from_secs_f32(TIME_STEP);
+    app.insert_resource(TimeUpdateStrategy::ManualInstant(*now));
+    app.update();
+}
+
+#[test]
+fn pausing_mid_shot_holds_the_game() {
+    let mut app = App::new();
+    app.add_plugin(TaskPoolPlugin::default())
+        .add_plugin(TypeRegistrationPlugin::default())
+        .add_plugin(FrameCountPlugin::default())
+        .add_plugin(AssetPlugin::default())
+        .add_plugin(ScenePlugin::default())
+        .add_asset::<Mesh>()
+        .add_asset::<Scene>()
+        .add_plugin(TimePlugin::default())
+        .add_plugin(RngPlugin::new().with_rng_seed(0772));
+    add_simulation(&mut app);
+    app.world
+        .resource_mut::<RapierConfiguration>()
+        .timestep_mode = TimestepMode::Fixed {
+        dt: TIME_STEP,
+        substeps: 1,
+    };
+    let mut now = Instant::now();
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &app.world);
+    field::spawn_ground(&mut commands);
+    field::spawn_goal(&mut commands, Arena::default());
+    let goalie = commands
+        .spawn(field::goalie_body(Arena::default(), GOALIE_START))
+        .insert(GoalieBehavior::new(Difficulty::Hard))
+        .id();
+    let ball = commands.spawn(field::ball_body(Arena::default())).id();
+    queue.apply(&mut app.world);
+
+    let kick_model = app.world.resource::<KickModel>().clone();
+    for _ in 0..600 {
+        let ball_ref = app.world.entity(ball);
+        let (state, transform) = (ball_ref.get::<Ball>(), ball_ref.get::<Transform>());
+        if kick_model.can_kick(state.unwrap(), transform.unwrap()) {
+            break;
+        }
+        step(&mut app, &mut now);
+    }
+
+    // the spacebar shot, it lifts and curls
+    let mut ball_ref = app.world.entity_mut(ball);
+    let transform = *ball_ref.get::<Transform>().unwrap();
+    let mut ext_i = *ball_ref.get::<ExternalImpulse>().unwrap();
+    let mut state = ball_ref.take::<Ball>().unwrap();
+    assert!(kick_model.kick(
+        Vec3::new(0.015694855, -0.011672409, 0.9998087),
+        Vec3::new(0.0017264052, 0.0070980787, 42.109978),
+        &transform,
+        &mut state,
+        &mut ext_i,
+    ));
+    ball_ref.insert((state, ext_i));
+    for _ in 0..10 {
+        step(&mut app, &mut now);
+    }
+
+    let snapshot = |app: &App| {
+        let ball_ref = app.world.entity(ball);
+        (
+            ball_ref.get::<Transform>().unwrap().translation,
+            ball_ref.get::<ExternalForce>().unwrap().force,
+            ball_ref.get::<Ball>().unwrap().shot_elapsed,
+            app.world.get::<Transform>(goalie).unwrap().translation,
+        )
+    };
+    let before = snapshot(&app);
+    assert!(before.0.y > BALL_RADIUS, "ball should be in the air");
+
+    app.world.resource_scope(|world, mut paused: Mut<Paused>| {
+        paused.set(&mut world.resource_mut::<RapierConfiguration>(), true);
+    });
+    // longer than a missed shot takes to reset
+    let paused_steps = (BALL_SHOT_WAIT_TIME * 2.0 / TIME_STEP) as usize;
+    for _ in 0..paused_steps {
+        step(&mut app, &mut now);
+    }
+
+    assert_eq!(snapshot(&app), before);
+    assert!(app.world.get::<Ball>(ball).unwrap().shot);
+}
@@ -0,0 +1,120 @@
+use powerbaby_server::auth::{
+    migrate_legacy_ids, Anonymous, Authenticator, HmacAuthenticator, PlayerId,
+};
+use protocol::{
+    messages::Auth,
+    primitives::{PlayColor, PlayerStats, Scores},
+};
+
+fn auth(name: &str, token: Option<&str>) -> Auth {
+    Auth::new(name, PlayColor::Blue).with_token(token.map(str::to_owned))
+}
+
+#[test]
+fn issued_tokens_verify() {
+    let authenticator = HmacAuthenticator::new("secret", false);
+    let token = authenticator.issue("alice");
+    assert_eq!(
+        authenticator.verify(&token),
+        Some(PlayerId("id:alice".to_owned()))
+    );
+    // ids may contain dots, the signature is after the last one
+    let token = authenticator.issue("alice.smith");
+    assert_eq!(
+        authenticator.verify(&token),
+        Some(PlayerId("id:alice.smith".to_owned()))
+    );
+}
+
+#[test]
+fn tampered_tokens_are_rejected() {
+    let authenticator = HmacAuthenticator::new("secret", false);
+    let token = authenticator.issue("alice");
+    let (_, signature) = token.rsplit_once('.').unwrap();
+    assert_eq!(authenticator.verify(&format!("bob.{signature}")), None);
+
+    let other = HmacAuthenticator::new("other secret", false);
+    assert_eq!(other.verify(&token), None);
+}
+
+#[test]
+fn malformed_tokens_are_rejected() {
+    let authenticator = HmacAuthenticator::new("secret", false);
+    let token = authenticator.issue("alice");
+    for token in [
+        "",
+        "alice",
+        "alice.",
+        "alice.zz",
+        "alice.abc",
+        &token[..token.len() - 1],
+        &format!(".{}", token.rsplit_once('.').unwrap().1),
+    ] {
+        assert_eq!(authenticator.verify(token), None, "{token:?}");
+    }
+}
+
+#[test]
+fn anonymous_players_need_permission() {
+    let strict = HmacAuthenticator::new("secret", false);
+    assert_eq!(strict.authenticate(&auth("alice", None)), None);
+
+    let lenient = HmacAuthenticator::new("secret", true);
+    let id = lenient.authenticate(&auth("Alice ", None)).unwrap();
+    assert!(id.is_anonymous());
+    assert_eq!(id, Anonymous.authenticate(&auth("alice", None)).unwrap());
+}
+
+#[test]
+fn names_cant_pass_for_token_holders() {
+    let authenticator = HmacAuthenticator::new("secret", true);
+    let token = authenticator.issue("alice");
+    let holder = authenticator
+        .authenticate(&auth("alice", Some(&token)))
+        .unwrap();
+    let impostor = authenticator.authenticate(&auth("id:alice", None)).unwrap();
+    assert!(!holder.is_anonymous());
+    assert_ne!(holder, impostor);
+}
+
+#[test]
+fn legacy_name_keys_move_to_anonymous_ids() {
+    let mut scores = Scores::default();
+    scores.personal_blue.insert("Jason".to_owned(), 2);
+    scores.personal_blue.insert("jason".to_owned(), 1);
+    // an old free-text name, not a token holder
+    scores.personal_blue.insert("id:alice".to_owned(), 5);
+    scores.stats.insert(
+        "Jason".to_owned(),
+        PlayerStats {
+            kicks: 3,
+            ..Default::default()
+        },
+    );
+    scores.names.insert("Jason".to_owned(), "Jason".to_owned());
+
+    migrate_legacy_ids(&mut scores);
+
+    let jason = PlayerId::anonymous("Jason").0;
+    assert_eq!(scores.version, Scores::VERSION);
+    assert_eq!(scores.personal_blue.len(), 2);
+    assert_eq!(scores.personal_blue[&jason], 3);
+    assert_eq!(scores.personal_blue[&PlayerId::anonymous("id:alice").0], 5);
+    assert!(!scores.personal_blue.contains_key("id:alice"));
+    assert_eq!(scores.stats[&jason].kicks, 3);
+    assert_eq!(scores.names[&jason], "Jason");
+}
+
+#[test]
+fn migrated_scores_are_left_alone() {
+    let mut scores = Scores {
+        version: Scores::VERSION,
+        ..Default::default()
+    };
+    scores.personal_blue.insert("id:alice".to_owned(), 5);
+    scores.personal_blue.insert("anon:jason".to_owned(), 2);
+    let before = scores.personal_blue.clone();
+
+    migrate_legacy_ids(&mut scores);
+    assert_eq!(scores.personal_blue, before);
+}
@@ -0,0 +1,69 @@
+use powerbaby_server::lag_compensation::{goalie_in_path, rewind_tick, REWIND_SLACK_TICKS};
+
+use core::{constants::*, goalie_ai::GoalieAction};
+
+use bevy::prelude::{Transform, Vec3};
+
+const GRAVITY: f32 = 9.81;
+
+/// The impulse that sends a ball from `BALL_START` through `target` on the goalie's line
+fn impulse_through(target: Vec3, vz: f32) -> Vec3 {
+    let t = (target.z - BALL_START.z) / vz;
+    let vx = (target.x - BALL_START.x) / t;
+    let vy = (target.y - BALL_START.y + 0.5 * GRAVITY * t * t) / t;
+    Vec3::new(vx, vy, vz) * BALL_MASS
+}
+
+fn goalie() -> Transform {
+    Transform::from_translation(GOALIE_START)
+}
+
+fn ball() -> Transform {
+    Transform::from_translation(BALL_START)
+}
+
+#[test]
+fn shot_at_the_goalie_is_blocked() {
+    let impulse = impulse_through(GOALIE_START, -20.0);
+    assert!(goalie_in_path(&goalie(), &ball(), impulse));
+}
+
+#[test]
+fn shot_beside_the_goalie_passes() {
+    let target = GOALIE_START + Vec3::X * 1.5;
+    let impulse = impulse_through(target, -20.0);
+    assert!(!goalie_in_path(&goalie(), &ball(), impulse));
+}
+
+#[test]
+fn ball_moving_away_is_not_blocked() {
+    let impulse = Vec3::new(0.0, 2.0, 20.0) * BALL_MASS;
+    assert!(!goalie_in_path(&goalie(), &ball(), impulse));
+    assert!(!goalie_in_path(&goalie(), &ball(), Vec3::ZERO));
+}
+
+#[test]
+fn diving_goalie_reaches_further() {
+    let target = Vec3::new(1.2, 0.2, GOALIE_START.z);
+    let impulse = impulse_through(target, -20.0);
+    assert!(!goalie_in_path(&goalie(), &ball(), impulse));
+
+    let diving = GoalieAction::DiveRight
+        .pose(GOALIE_DIVE_TIME * 0.5)
+        .apply(GOALIE_START);
+    assert!(goalie_in_path(&diving, &ball(), impulse));
+}
+
+#[test]
+fn seen_ticks_older_than_the_round_trip_are_rejected() {
+    // 100ms is 3 ticks of 40ms
+    let oldest = 3 + REWIND_SLACK_TICKS;
+    assert_eq!(rewind_tick(1000, 1000, 100.0), Some(1000));
+    assert_eq!(rewind_tick(1000, 1000 - oldest, 100.0), Some(1000 - oldest));
+    assert_eq!(rewind_tick(1000, 1000 - oldest - 1, 100.0), None);
+    assert_eq!(rewind_tick(1000, 1000 - 31, 0.0), None);
+    // from the future
+    assert_eq!(rewind_tick(1000, 1001, 100.0), None);
+    // across the wrap
+    assert_eq!(rewind_tick(2, u16::MAX, 100.0), Some(u16::MAX));
+}
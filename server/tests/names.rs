@@ -0,0 +1,85 @@
+use powerbaby_server::names::{fold, normalize, NameCollision, NamePolicy};
+use protocol::primitives::RejectReason;
+
+fn policy(collision: NameCollision) -> NamePolicy {
+    NamePolicy {
+        blocklist: vec![fold("Badword")],
+        collision,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn normalize_trims_and_composes() {
+    assert_eq!(normalize("  Jason \t"), "Jason");
+    // "e" followed by a combining acute accent
+    assert_eq!(normalize("Jose\u{301}"), "José");
+}
+
+#[test]
+fn fold_ignores_case_spacing_and_composition() {
+    assert_eq!(fold("Jason"), fold(" JASON "));
+    assert_eq!(fold("José"), fold("JOSE\u{301}"));
+    // full case folding, lowercasing alone keeps the ß
+    assert_eq!(fold("Straße"), fold("STRASSE"));
+    assert_ne!(fold("Jason"), fold("Jason 2"));
+}
+
+#[test]
+fn validate_rejects_bad_names() {
+    let policy = policy(NameCollision::Reject);
+    assert_eq!(policy.validate(" Jason "), Ok("Jason".to_owned()));
+    assert_eq!(policy.validate("   "), Err(RejectReason::NameInvalid));
+    assert_eq!(
+        policy.validate(&"x".repeat(policy.max_length + 1)),
+        Err(RejectReason::NameInvalid)
+    );
+    assert_eq!(
+        policy.validate("Ja\u{7}son"),
+        Err(RejectReason::NameInvalid)
+    );
+    assert_eq!(
+        policy.validate("xxBADWORDxx"),
+        Err(RejectReason::NameInvalid)
+    );
+}
+
+#[test]
+fn collisions_are_checked_folded() {
+    let taken = |folded: &str| folded == fold("Jason");
+
+    let reject = policy(NameCollision::Reject);
+    assert_eq!(
+        reject.resolve_collision("JASON".to_owned(), taken),
+        Err(RejectReason::NameTaken)
+    );
+    assert_eq!(
+        reject.resolve_collision("Ana".to_owned(), taken),
+        Ok("Ana".to_owned())
+    );
+
+    let suffix = policy(NameCollision::Suffix);
+    assert_eq!(
+        suffix.resolve_collision("JASON".to_owned(), taken),
+        Ok("JASON 2".to_owned())
+    );
+    let taken = |folded: &str| folded == fold("Jason") || folded == fold("jason 2");
+    assert_eq!(
+        suffix.resolve_collision("Jason".to_owned(), taken),
+        Ok("Jason 3".to_owned())
+    );
+}
+
+#[test]
+fn suffixed_names_fit_the_max_length() {
+    let suffix = policy(NameCollision::Suffix);
+    let long = "x".repeat(suffix.max_length);
+    let renamed = suffix.resolve_collision(long, |_| true);
+    assert_eq!(renamed, Err(RejectReason::NameTaken));
+
+    let long = "y".repeat(suffix.max_length);
+    let taken = |folded: &str| folded == fold(&"y".repeat(suffix.max_length));
+    let renamed = suffix.resolve_collision(long, taken).unwrap();
+    assert_eq!(renamed.chars().count(), suffix.max_length);
+    assert!(renamed.ends_with(" 2"));
+}
@@ -0,0 +1,181 @@
+use powerbaby_server::{
+    auth::PlayerId,
+    registry::{Pending, PlayerRegistry, PENDING_TIMEOUT},
+};
+use protocol::{
+    components::Player,
+    primitives::{PlayColor, RejectReason},
+};
+
+use bevy::prelude::Entity;
+
+type Registry = PlayerRegistry<u32>;
+
+fn player(name: &str) -> Pending {
+    Pending::Player {
+        player: Player::new(name.to_owned(), PlayColor::Blue),
+        player_id: PlayerId(name.to_owned()),
+        room: "main".to_owned(),
+    }
+}
+
+#[test]
+fn player_lifecycle() {
+    let mut registry = Registry::default();
+    let ball = Entity::from_raw(7);
+
+    registry.admit(1, player("ana"));
+    assert_eq!(registry.pending_players().count(), 1);
+    registry.assert_invariants();
+
+    assert!(matches!(
+        registry.take_pending(&1),
+        Some(Pending::Player { .. })
+    ));
+    registry.connect_player(1, ball);
+    registry.assert_invariants();
+    assert_eq!(registry.ball_of(&1), Some(ball));
+    assert_eq!(registry.owner_of(ball), Some(1));
+    assert_eq!(registry.pending_players().count(), 0);
+
+    assert_eq!(registry.disconnect(&1), Some(ball));
+    registry.assert_invariants();
+    assert_eq!(registry.owner_of(ball), None);
+    assert_eq!(registry.player_count(), 0);
+}
+
+#[test]
+fn disconnect_forgets_the_owner() {
+    let mut registry = Registry::default();
+    registry.connect_player(1, Entity::from_raw(1));
+    registry.connect_player(2, Entity::from_raw(2));
+
+    registry.disconnect(&1);
+    registry.assert_invariants();
+    assert_eq!(registry.owner_of(Entity::from_raw(1)), None);
+    assert_eq!(registry.owner_of(Entity::from_raw(2)), Some(2));
+}
+
+#[test]
+fn resumed_ball_changes_owner() {
+    let mut registry = Registry::default();
+    let ball = Entity::from_raw(3);
+    registry.connect_player(1, ball);
+    registry.connect_player(2, ball);
+    registry.assert_invariants();
+    assert_eq!(registry.owner_of(ball), Some(2));
+    assert_eq!(registry.ball_of(&1), None);
+}
+
+#[test]
+fn spectators_have_no_ball() {
+    let mut registry = Registry::default();
+    registry.admit(
+        1,
+        Pending::Spectator {
+            room: "main".to_owned(),
+        },
+    );
+    registry.take_pending(&1);
+    registry.connect_spectator(1);
+    registry.assert_invariants();
+    assert!(registry.is_spectator(&1));
+    assert_eq!(registry.ball_of(&1), None);
+
+    assert_eq!(registry.disconnect(&1), None);
+    assert!(!registry.is_spectator(&1));
+}
+
+#[test]
+fn admitted_users_that_never_connect_expire() {
+    let mut registry = Registry::default();
+    registry.admit(1, player("ana"));
+    registry.admit(2, Pending::Rejected(RejectReason::ServerFull));
+
+    assert!(registry.expire_pending(PENDING_TIMEOUT / 2.0).is_empty());
+    registry.admit(3, player("bo"));
+    let mut expired = registry.expire_pending(PENDING_TIMEOUT / 2.0 + 1.0);
+    expired.sort();
+    assert_eq!(expired, vec![1, 2]);
+    assert!(registry.is_pending(&3));
+    assert!(registry.take_pending(&1).is_none());
+    registry.assert_invariants();
+}
+
+#[test]
+fn disconnect_before_connecting_drops_the_admission() {
+    let mut registry = Registry::default();
+    registry.admit(1, player("ana"));
+    assert_eq!(registry.disconnect(&1), None);
+    assert!(!registry.is_pending(&1));
+    assert_eq!(registry.pending_players().count(), 0);
+}
+
+#[test]
+fn held_players_keep_their_ball_until_resumed() {
+    let mut registry = Registry::default();
+    let ball = Entity::from_raw(4);
+    registry.connect_player(1, ball);
+
+    assert_eq!(registry.hold(1), Some(ball));
+    registry.assert_invariants();
+    assert!(registry.is_held(&1));
+    assert_eq!(registry.owner_of(ball), Some(1));
+    assert_eq!(registry.players().count(), 0);
+    assert_eq!(registry.player_count(), 0);
+
+    registry.connect_player(2, ball);
+    registry.assert_invariants();
+    assert!(!registry.is_held(&1));
+    assert_eq!(registry.owner_of(ball), Some(2));
+    assert_eq!(registry.player_count(), 1);
+}
+
+#[test]
+fn expired_holds_are_forgotten() {
+    let mut registry = Registry::default();
+    let ball = Entity::from_raw(5);
+    registry.connect_player(1, ball);
+    registry.hold(1);
+
+    assert_eq!(registry.disconnect(&1), Some(ball));
+    registry.assert_invariants();
+    assert!(!registry.is_held(&1));
+    assert_eq!(registry.owner_of(ball), None);
+    assert_eq!(registry.hold(1), None);
+}
+
+#[test]
+fn goalies_take_a_player_slot() {
+    let mut registry = Registry::default();
+    registry.connect_player(1, Entity::from_raw(1));
+    registry.admit(
+        2,
+        Pending::Goalie {
+            room: "main".to_owned(),
+        },
+    );
+    registry.admit(3, player("ana"));
+    registry.admit(
+        4,
+        Pending::Spectator {
+            room: "main".to_owned(),
+        },
+    );
+    assert_eq!(registry.slots_taken(), 3);
+
+    registry.take_pending(&2);
+    registry.connect_goalie(2);
+    registry.assert_invariants();
+    assert!(registry.is_goalie(&2));
+    // goalies see every ball like spectators do
+    assert!(registry.is_spectator(&2));
+    assert_eq!(registry.player_count(), 2);
+    assert_eq!(registry.spectator_count(), 0);
+    assert_eq!(registry.slots_taken(), 3);
+
+    registry.disconnect(&2);
+    registry.assert_invariants();
+    assert!(!registry.is_goalie(&2));
+    assert_eq!(registry.slots_taken(), 2);
+}
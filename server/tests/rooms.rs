@@ -0,0 +1,28 @@
+use powerbaby_server::rooms::{room_code, MAIN_ROOM};
+
+#[test]
+fn no_code_is_the_main_room() {
+    assert_eq!(room_code(None), MAIN_ROOM);
+    assert_eq!(room_code(Some("")), MAIN_ROOM);
+    assert_eq!(room_code(Some("   ")), MAIN_ROOM);
+}
+
+#[test]
+fn codes_are_trimmed_and_lowercased() {
+    assert_eq!(room_code(Some("office-42")), "office-42");
+    assert_eq!(room_code(Some(" Office-42 ")), "office-42");
+    assert_eq!(room_code(Some(MAIN_ROOM)), MAIN_ROOM);
+}
+
+#[test]
+fn invalid_codes_are_the_main_room() {
+    for code in ["office 42", "office_42", "bür", "../main", "a;b"] {
+        assert_eq!(room_code(Some(code)), MAIN_ROOM, "{code:?}");
+    }
+}
+
+#[test]
+fn codes_are_at_most_16_characters() {
+    assert_eq!(room_code(Some(&"a".repeat(16))), "a".repeat(16));
+    assert_eq!(room_code(Some(&"a".repeat(17))), MAIN_ROOM);
+}
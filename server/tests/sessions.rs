@@ -0,0 +1,70 @@
+use powerbaby_server::{auth::PlayerId, sessions::Sessions};
+
+use bevy::prelude::Entity;
+
+type UserSessions = Sessions<u32>;
+
+fn start(sessions: &mut UserSessions, user_key: u32) -> String {
+    sessions.start(
+        user_key,
+        Entity::from_raw(user_key),
+        PlayerId(format!("id:{user_key}")),
+    )
+}
+
+#[test]
+fn only_dropped_sessions_can_be_resumed() {
+    let mut sessions = UserSessions::default();
+    let token = start(&mut sessions, 1);
+    assert!(sessions.held(&token).is_none());
+    assert!(sessions.resume(&token, 2).is_none());
+
+    assert!(sessions.hold(&1));
+    assert!(!sessions.hold(&3));
+    assert_eq!(sessions.held(&token).unwrap().ball, Entity::from_raw(1));
+
+    let dropped = sessions.resume(&token, 2).unwrap();
+    assert_eq!(dropped.user_key, 1);
+    assert_eq!(dropped.player_id, PlayerId("id:1".to_owned()));
+    assert!(sessions.held(&token).is_none());
+    // the session follows the new user
+    assert!(sessions.hold(&2));
+    assert!(sessions.held(&token).is_some());
+}
+
+#[test]
+fn held_sessions_expire_after_the_grace() {
+    let mut sessions = UserSessions::default();
+    let token = start(&mut sessions, 1);
+    let connected = start(&mut sessions, 2);
+    sessions.hold(&1);
+
+    assert!(sessions.expire(1.0, 1.5).is_empty());
+    let expired = sessions.expire(1.0, 1.5);
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].user_key, 1);
+    assert!(sessions.resume(&token, 3).is_none());
+
+    // connected users never expire
+    assert!(sessions.expire(100.0, 1.5).is_empty());
+    sessions.hold(&2);
+    assert!(sessions.held(&connected).is_some());
+}
+
+#[test]
+fn ended_sessions_are_forgotten() {
+    let mut sessions = UserSessions::default();
+    let token = start(&mut sessions, 1);
+    sessions.end(&1);
+    assert!(!sessions.hold(&1));
+    assert!(sessions.held(&token).is_none());
+}
+
+#[test]
+fn tokens_are_128_random_bits() {
+    let mut sessions = UserSessions::default();
+    let token = start(&mut sessions, 1);
+    assert_eq!(token.len(), 32);
+    assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_ne!(token, start(&mut sessions, 2));
+}
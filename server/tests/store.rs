@@ -0,0 +1,165 @@
+use powerbaby_server::store::{JsonFileStore, ScoreStore};
+use protocol::primitives::Scores;
+
+use std::{fs, path::PathBuf};
+
+/// A fresh directory per test, tests run in parallel
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("powerbaby-store-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("create temp dir");
+    dir
+}
+
+fn scores(blue_total: u32) -> Scores {
+    Scores {
+        blue_total,
+        ..Default::default()
+    }
+}
+
+fn backup(path: &PathBuf, n: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{n}", path.display()))
+}
+
+#[test]
+fn missing_file_loads_empty_scores() {
+    let dir = temp_dir("missing");
+    let store = JsonFileStore::new(dir.join("scores.json"), 3);
+    let loaded = store.load().expect("load");
+    assert_eq!(loaded.blue_total, 0);
+    assert!(loaded.stats.is_empty());
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn save_then_load_round_trips() {
+    let dir = temp_dir("round-trip");
+    let store = JsonFileStore::new(dir.join("scores.json"), 3);
+    let mut saved = scores(3);
+    saved.pink_total = 5;
+    saved.personal_blue.insert("id:ana".to_owned(), 2);
+    saved.stats_mut("id:ana").kicks = 7;
+    saved.names.insert("id:ana".to_owned(), "Ana".to_owned());
+    saved.version = Scores::VERSION;
+    store.save(&saved).expect("save");
+
+    let loaded = store.load().expect("load");
+    assert_eq!(loaded.blue_total, 3);
+    assert_eq!(loaded.pink_total, 5);
+    assert_eq!(loaded.personal_blue, saved.personal_blue);
+    assert_eq!(loaded.stats["id:ana"].kicks, 7);
+    assert_eq!(loaded.names, saved.names);
+    assert_eq!(loaded.version, Scores::VERSION);
+    assert!(!dir.join("scores.json.tmp").exists());
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn saves_keep_the_newest_backups() {
+    let dir = temp_dir("rotate");
+    let path = dir.join("scores.json");
+    let store = JsonFileStore::new(&path, 2);
+    for blue_total in 1..=4 {
+        store.save(&scores(blue_total)).expect("save");
+    }
+
+    let read = |path: &PathBuf| JsonFileStore::new(path, 0).load().expect("load").blue_total;
+    assert_eq!(read(&path), 4);
+    assert_eq!(read(&backup(&path, 1)), 3);
+    assert_eq!(read(&backup(&path, 2)), 2);
+    assert!(!backup(&path, 3).exists());
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn corrupt_file_falls_back_to_the_newest_backup() {
+    let dir = temp_dir("corrupt");
+    let path = dir.join("scores.json");
+    let store = JsonFileStore::new(&path, 3);
+    store.save(&scores(1)).expect("save");
+    store.save(&scores(2)).expect("save");
+    fs::write(&path, b"{ not json").expect("corrupt");
+
+    assert_eq!(store.load().expect("recovered").blue_total, 1);
+
+    fs::write(backup(&path, 1), b"").expect("corrupt");
+    assert!(store.load().is_err());
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn failed_save_leaves_the_old_file_and_no_temp_file() {
+    let dir = temp_dir("failed-save");
+    let path = dir.join("scores.json");
+    let store = JsonFileStore::new(&path, 1);
+    store.save(&scores(1)).expect("save");
+    // the backup can't be written over a directory
+    fs::create_dir(backup(&path, 1)).expect("block backup");
+    fs::write(backup(&path, 1).join("keep"), b"").expect("fill backup dir");
+
+    assert!(store.save(&scores(2)).is_err());
+    assert_eq!(store.load().expect("load").blue_total, 1);
+    assert!(!dir.join("scores.json.tmp").exists());
+    fs::remove_dir_all(dir).ok();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_save_then_load_round_trips() {
+    use powerbaby_server::store::SqliteStore;
+
+    let dir = temp_dir("sqlite-round-trip");
+    let path = dir.join("scores.db");
+    let mut saved = scores(3);
+    saved.pink_total = 5;
+    saved.personal_pink.insert("id:ana".to_owned(), 2);
+    saved.stats_mut("id:ana").kicks = 7;
+    saved.stats_mut("id:ana").last_seen = 1_700_000_000;
+    saved.names.insert("id:ana".to_owned(), "Ana".to_owned());
+    saved.version = Scores::VERSION;
+    SqliteStore::open(&path)
+        .expect("open")
+        .save(&saved)
+        .expect("save");
+
+    // a new connection sees the committed scores
+    let loaded = SqliteStore::open(&path)
+        .expect("reopen")
+        .load()
+        .expect("load");
+    assert_eq!(loaded.blue_total, 3);
+    assert_eq!(loaded.pink_total, 5);
+    assert_eq!(loaded.personal_pink, saved.personal_pink);
+    assert!(loaded.personal_blue.is_empty());
+    assert_eq!(loaded.stats["id:ana"].kicks, 7);
+    assert_eq!(loaded.stats["id:ana"].last_seen, 1_700_000_000);
+    assert_eq!(loaded.names, saved.names);
+    assert_eq!(loaded.version, Scores::VERSION);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_renames_legacy_name_columns() {
+    use powerbaby_server::store::SqliteStore;
+
+    let dir = temp_dir("sqlite-legacy");
+    let path = dir.join("scores.db");
+    let conn = rusqlite::Connection::open(&path).expect("create");
+    conn.execute_batch(
+        "CREATE TABLE personal (color TEXT NOT NULL, name TEXT NOT NULL, goals INTEGER NOT NULL,
+         PRIMARY KEY (color, name));
+         INSERT INTO personal VALUES ('blue', 'Jason', 4);",
+    )
+    .expect("legacy schema");
+    drop(conn);
+
+    let loaded = SqliteStore::open(&path)
+        .expect("open")
+        .load()
+        .expect("load");
+    assert_eq!(loaded.personal_blue["Jason"], 4);
+    assert_eq!(loaded.version, 0);
+    fs::remove_dir_all(dir).ok();
+}
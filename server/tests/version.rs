@@ -0,0 +1,54 @@
+use powerbaby_server::version::is_compatible;
+use protocol::{primitives::ProtocolVersion, PROTOCOL_VERSION};
+
+#[test]
+fn own_version_is_compatible() {
+    assert!(is_compatible(&PROTOCOL_VERSION));
+}
+
+#[test]
+fn older_minor_and_patch_are_compatible() {
+    let ProtocolVersion {
+        major,
+        minor,
+        patch,
+    } = PROTOCOL_VERSION;
+    assert!(is_compatible(&ProtocolVersion::new(major, 0, 0)));
+    if patch > 0 {
+        assert!(is_compatible(&ProtocolVersion::new(
+            major,
+            minor,
+            patch - 1
+        )));
+    }
+}
+
+#[test]
+fn newer_clients_are_rejected() {
+    let ProtocolVersion {
+        major,
+        minor,
+        patch,
+    } = PROTOCOL_VERSION;
+    assert!(!is_compatible(&ProtocolVersion::new(
+        major,
+        minor,
+        patch + 1
+    )));
+    assert!(!is_compatible(&ProtocolVersion::new(major, minor + 1, 0)));
+    assert!(!is_compatible(&ProtocolVersion::new(major + 1, 0, 0)));
+}
+
+#[test]
+fn other_majors_are_rejected() {
+    // 2.1 to 2.4 changed `Auth` and the message ids without a major bump
+    for minor in 0..=4 {
+        assert!(!is_compatible(&ProtocolVersion::new(2, minor, 0)));
+    }
+    assert!(!is_compatible(&ProtocolVersion::new(1, 0, 0)));
+    assert!(!is_compatible(&ProtocolVersion::new(
+        PROTOCOL_VERSION.major - 1,
+        u16::MAX,
+        u16::MAX
+    )));
+}
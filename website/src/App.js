@@ -47,6 +47,8 @@ function App() {
       <Leaderboard
         pinkScores={scores.personal_pink}
         blueScores={scores.personal_blue}
+        stats={scores.stats || {}}
+        names={scores.names || {}}
       />
       </header>
     </div>
@@ -115,7 +117,16 @@ const YoutubeEmbed = ({ embedId }) => (
   </div>
 );
 
-const Leaderboard = ({ pinkScores, blueScores }) => {
+// goals per kick, blank for players from before stats were tracked
+const accuracy = (stats, player) => {
+  const s = stats[player];
+  if (!s || s.kicks === 0) {
+    return "";
+  }
+  return `${Math.round((s.goals / s.kicks) * 100)}%`;
+};
+
+const Leaderboard = ({ pinkScores, blueScores, stats, names }) => {
   const maxPinkScore = Math.max(...Object.values(pinkScores));
   const maxBlueScore = Math.max(...Object.values(blueScores));
   const maxScore = Math.max(maxPinkScore, maxBlueScore);
@@ -124,8 +135,9 @@ const Leaderboard = ({ pinkScores, blueScores }) => {
     .sort((a, b) => pinkScores[b] - pinkScores[a])
     .map((player) => (
       <div key={player} className="row">
-        <div className="player">{player}</div>
+        <div className="player">{names[player] || player}</div>
         <div className="score">{pinkScores[player]}</div>
+        <div className="accuracy">{accuracy(stats, player)}</div>
         <div className="bar" style={{ width: `${(pinkScores[player] / maxScore) * 100}%` }}></div>
       </div>
     ));
@@ -134,8 +146,9 @@ const Leaderboard = ({ pinkScores, blueScores }) => {
     .sort((a, b) => blueScores[b] - blueScores[a])
     .map((player) => (
       <div key={player} className="row">
-        <div className="player">{player}</div>
+        <div className="player">{names[player] || player}</div>
         <div className="score">{blueScores[player]}</div>
+        <div className="accuracy">{accuracy(stats, player)}</div>
         <div className="bar" style={{ width: `${(blueScores[player] / maxScore) * 100}%` }}></div>
       </div>
     ));